@@ -0,0 +1,78 @@
+use std::env;
+use std::process;
+
+use ribbit::rvm::{read_rvm_code, translate, RunStatus, Vm};
+
+// usage: rvm [--incremental-gc] [--max-heap-size RIBS]
+//            [--heap-growth PERCENT] [file.rvm]
+//        rvm --emit-rust [file.rvm]
+//
+// Runs the compacted RVM code found in file.rvm. The VM checks the number
+// of arguments of calls and numbers its primitives as in rvm.rs, the first
+// 21 being those every host has, so the code must be compiled for the rvm
+// target with the arity-check feature, with any library:
+//
+//        gsi rsc.scm -t rvm -f+ arity-check -l max file.scm
+//
+// or `./rsc -t rvm -l max file.scm`, as the compiler the rsc script runs
+// always enables arity-check. Code compiled for the rs target is not
+// suitable: rsc numbers the primitives it uses in another order. Without a
+// file (or with `-`), the code is read from the first line of standard
+// input and the following lines are the input of the program.
+//
+//   --incremental-gc         do major garbage collections a little at each
+//                            instruction instead of pausing the program
+//   --max-heap-size RIBS     bound the heap, see `Vm::set_max_heap_size`
+//   --heap-growth PERCENT    set how much the heap grows between major
+//                            collections, see `Vm::set_heap_growth`
+//   --emit-rust              do not run the program but translate it to a
+//                            Rust module that runs it with native code,
+//                            see `translate`
+
+fn main() {
+    let mut vm = Vm::new();
+    let mut path = String::from("-");
+    let mut emit_rust = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--incremental-gc" => vm.set_incremental_gc(true),
+            "--max-heap-size" => vm.set_max_heap_size(Some(number_arg(&arg, args.next()))),
+            "--heap-growth" => vm.set_heap_growth(number_arg(&arg, args.next())),
+            "--emit-rust" => emit_rust = true,
+            _ => path = arg,
+        }
+    }
+
+    let rvm_code = read_rvm_code(&path).unwrap_or_else(|err| {
+        eprintln!("Cannot read RVM code from {}: {}", path, err);
+        process::exit(1)
+    });
+
+    if emit_rust {
+        match translate(&rvm_code) {
+            Ok(module) => print!("{}", module),
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1);
+            },
+        }
+        return
+    }
+
+    match vm.load(&rvm_code).and_then(|()| vm.run()) {
+        Ok(RunStatus::Exited(status)) => process::exit(status),
+        Ok(_) => (),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        },
+    }
+}
+
+fn number_arg(option: &str, value: Option<String>) -> usize {
+    value.and_then(|value| value.parse().ok()).unwrap_or_else(|| {
+        eprintln!("{} expects a number", option);
+        process::exit(1)
+    })
+}
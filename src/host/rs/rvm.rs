@@ -1,10 +1,8 @@
 pub mod rvm {
+    use std::any::{type_name, Any};
     use std::fmt::{Display, Formatter};
     use std::cmp::Ordering;
-    use std::cmp::Ordering::Equal;
-    use std::io::*;
-    use std::ops::{Add, Div, Mul, Sub};
-    use std::process;
+    use std::io::{stdin, stdout, BufRead, BufReader, BufWriter, Read, Write};
 
 
     // Data representation as Rib
@@ -13,16 +11,30 @@ pub mod rvm {
 // Closure Procedure: code,env,1 (Rib,Rib,1)
 //       code: nparams,0,start (int,0,Rib: Operation)
 // Primitive Procedure: code,dontcare,1 ([0..19],0,1)
+// Host Primitive Procedure: code,dontcare,1 (FIRST_HOST_PRIMITIVE+i,0,1)
 // Symbol: value,name,2 (Rib, Rib: String,2)
 // String: chars,length,3 (Rib,int,3)
 // Vector: elems,length,4 (Rib,int,4)
 // #t,#f,(): dontcare,dontcare,5 (0,0,5)
-    const PAIR: i32 = 0;
-    const PROCEDURE: i32 = 1;
-    const SYMBOL: i32 = 2;
-    const STRING: i32 = 3;
-    const VECTOR: i32 = 4;
-    const SPECIAL: i32 = 5;
+// Foreign: 0,index,6 (0,int,6)
+// Bignum: digits,sign,7 (Rib,int,7)
+// Flonum: high bits,low bits,8 (int,int,8)
+// Weak box: object,0,9 (Rib,0,9)
+// Ephemeron: key,value,10 (Rib,Rib,10)
+// Guarded: object,next,11 (Rib,Rib,11), a pair once object is dead
+    const PAIR: i64 = 0;
+    const PROCEDURE: i64 = 1;
+    const SYMBOL: i64 = 2;
+    const STRING: i64 = 3;
+    const VECTOR: i64 = 4;
+    const SPECIAL: i64 = 5;
+    const FOREIGN: i64 = 6;
+    const BIGNUM: i64 = 7;
+    const FLONUM: i64 = 8;
+    const WEAK_BOX: i64 = 9;
+    const EPHEMERON: i64 = 10;
+    const GUARDED: i64 = 11;
+    const ERROR_OBJECT: i64 = 14;
 
 
     // Operation representation as Rib
@@ -34,43 +46,223 @@ pub mod rvm {
 // const: 3,object,next (3,Rib, Rib: Operation)
 // if: 4,then,next (4,Rib: Operation, Rib: Operation)
 
-    const CALL: i32 = 0;
-    const SET: i32 = 1;
-    const GET: i32 = 2;
-    const CNST: i32 = 3;
-    const IF: i32 = 4;
-    const HALT: i32 = 5;
+    const CALL: i64 = 0;
+    const SET: i64 = 1;
+    const GET: i64 = 2;
+    const CNST: i64 = 3;
+    const IF: i64 = 4;
+    const HALT: i64 = 5;
+
+    // An operation rib decoded, with the index of the operation that follows
+    #[derive(Copy,Clone)]
+    enum Instr {
+        Call { opnd: RibField, next: RibField }, // next is 0 for a jump
+        Set { opnd: RibField, next: usize },
+        Get { opnd: RibField, next: usize },
+        Const { value: RibField, next: usize },
+        If { then: usize, next: usize },
+        Halt(RibField),
+    }
+
+    impl Instr {
+        fn decode(rib: &Rib) -> Result<Instr, VmError> {
+            let o = rib.middle;
+            Ok(match rib.first.get_number()? {
+                CALL => Instr::Call { opnd: o, next: rib.last },
+                SET => Instr::Set { opnd: o, next: rib.last.get_rib_ref()? },
+                GET => Instr::Get { opnd: o, next: rib.last.get_rib_ref()? },
+                CNST => Instr::Const { value: o, next: rib.last.get_rib_ref()? },
+                IF => Instr::If { then: o.get_rib_ref()?, next: rib.last.get_rib_ref()? },
+                HALT => Instr::Halt(o),
+                op => return Err(VmError::BadInstruction(op)),
+            })
+        }
+    }
+
+    // The operations of the old generation decoded by the interpreter, each
+    // once. Their ribs stay authoritative: the operations are decoded again
+    // when one of them is written, and when a major collection moves them.
+    // The blocks of native code given to Vm::set_native_code are found the
+    // same way, by the index of their first operation, after each major
+    // collection. They are not used anymore once one of their operations is
+    // written.
+    struct Code {
+        decoded: Vec<u32>, // for each old rib, 1 + the index of its operation in instrs, or 0
+        instrs: Vec<Instr>,
+        enabled: bool,
+        native: Vec<u32>, // for each old rib, 1 + the index of the block it starts, IN_BLOCK in a block, or 0
+        native_written: bool,
+    }
+
+    const IN_BLOCK: u32 = u32::MAX;
+
+    impl Code {
+        fn forget(&mut self) {
+            self.decoded.clear();
+            self.instrs.clear();
+        }
+
+        // The operations were moved by a major collection
+        fn moved(&mut self) {
+            self.forget();
+            self.native.clear();
+        }
+
+        fn is_decoded(&self, i: usize) -> bool {
+            self.decoded.get(i).is_some_and(|&d| d != 0)
+        }
+
+        fn is_native(&self, i: usize) -> bool {
+            self.native.get(i).is_some_and(|&b| b != 0)
+        }
+
+        // The index of the native block that starts at pc
+        fn native_block(&self, pc: usize) -> Option<usize> {
+            match self.native.get(pc) {
+                Some(&b) if b != 0 && b != IN_BLOCK => Some(b as usize - 1),
+                _ => None,
+            }
+        }
+    }
 
 
 
 
     // putchar
 
-    fn putchar(c: char) {
-        let mut stdo = stdout();
-        let binding = c.to_string();
-        let c_buffer =binding.as_bytes();
-        stdo.write(c_buffer)
-            .expect("Failed to write to stdo buffer");
-        stdo.flush()
-            .expect("Failed to flush stdo buffer");
+    // The output is buffered by the VM, see flush_output for when it is
+    // actually written
+    fn putchar<W: Write>(c: char, output: &mut W) -> Result<(), VmError> {
+        let mut buf = [0; 4];
+        output.write_all(c.encode_utf8(&mut buf).as_bytes())
+            .map_err(|err| VmError::Io(format!("Failed to write output: {}", err)))
+    }
+
+    // Called before reading input (so prompts appear), on exit, when the
+    // program halts and by the flush-output primitive
+    fn flush_output<W: Write>(output: &mut W) -> Result<(), VmError> {
+        output.flush()
+            .map_err(|err| VmError::Io(format!("Failed to flush output: {}", err)))
+    }
+
+    // Where the output of the program goes, see Vm::set_output and
+    // Vm::capture_output
+    enum Output {
+        Writer(Box<dyn Write>),
+        Captured(Vec<u8>),
+    }
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            match self {
+                Output::Writer(writer) => writer.write(buf),
+                Output::Captured(captured) => captured.write(buf),
+            }
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            match self {
+                Output::Writer(writer) => writer.flush(),
+                Output::Captured(_) => Ok(()),
+            }
+        }
     }
 
-    fn decode_char_to_u32(c: Option<char>) -> u32 {
+    fn decode_char_to_u32(c: Option<char>) -> Result<u32, VmError> {
         match c {
-            Some(ch) => ch as u32,
-            None => panic!("Unexpected end of input"),
+            Some(ch) => Ok(ch as u32),
+            None => Err(VmError::TruncatedBytecode),
         }
     }
 
+    fn to_char(n: i64) -> Result<char, VmError> {
+        if (0..=0x10FFFF).contains(&n) {
+            if let Some(c) = std::char::from_u32(n as u32) {
+                return Ok(c)
+            }
+        }
+        Err(VmError::TypeError(format!("expected representable character, got {}",n)))
+    }
+
+
+    /// An error raised by the VM while decoding or executing a program.
+    #[derive(Debug,Clone,PartialEq,Eq)]
+    pub enum VmError {
+        /// An operand does not have the type the operation expects.
+        TypeError(String),
+        /// The divisor of a `quotient` is zero.
+        DivisionByZero,
+        /// A procedure was called with `nargs` arguments but takes
+        /// `expected_nargs` of them, or at least that many when `variadic`.
+        ArityMismatch { nargs: u32, expected_nargs: u32, variadic: bool },
+        /// A primitive procedure has a code the VM does not implement.
+        BadPrimitive(i64),
+        /// An instruction has an opcode the VM does not implement.
+        BadInstruction(i64),
+        /// The RVM code ends before the program is fully decoded.
+        TruncatedBytecode,
+        /// Reading the input or writing the output of the program failed.
+        Io(String),
+        /// The loaded program has no global variable with this name.
+        UnknownGlobal(String),
+        /// The live objects of the program leave too little room on a heap
+        /// limited with `Vm::set_max_heap_size`.
+        HeapExhausted,
+        /// The native code given to `Vm::set_native_code` was not
+        /// translated from the loaded program.
+        NativeCodeMismatch,
+        /// The handle was not made by this heap, or was released by
+        /// `Vm::reset`.
+        UnknownHandle,
+        /// The procedure called with `Vm::call` did not return to its
+        /// caller but escaped to a continuation of the program, with a
+        /// continuation or an exception handler, and the program halted.
+        CallEscaped,
+        /// The exception handler given by the program, or the one called
+        /// with this error, is not a procedure taking one argument.
+        RejectedHandler(Box<VmError>),
+        /// The program called `exit` with this status. `run_for` reports it
+        /// as `RunStatus::Exited`, but `call` returns it as an error.
+        Exited(i32),
+    }
+
+    impl Display for VmError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            match self {
+                VmError::TypeError(message) => write!(f,"{}",message),
+                VmError::DivisionByZero => write!(f,"Division by zero"),
+                VmError::ArityMismatch { nargs, expected_nargs, variadic: true } =>
+                    write!(f,"Insufficient number of arguments. This function requires a minimum of {} arguments, got {}", expected_nargs, nargs),
+                VmError::ArityMismatch { nargs, expected_nargs, variadic: false } =>
+                    write!(f,"Incorrect number of arguments. This function takes {} arguments, got {}", expected_nargs, nargs),
+                VmError::BadPrimitive(code) => write!(f,"Unexpected code for primitive call {}",code),
+                VmError::BadInstruction(op) => write!(f,"Unimplemented instruction number {}",op),
+                VmError::TruncatedBytecode => write!(f,"Unexpected end of input"),
+                VmError::Io(message) => write!(f,"{}",message),
+                VmError::UnknownGlobal(name) => write!(f,"Unknown global variable {}",name),
+                VmError::HeapExhausted => write!(f,"Heap exhausted"),
+                VmError::NativeCodeMismatch => write!(f,"The native code does not match the loaded program"),
+                VmError::UnknownHandle => write!(f,"Unknown handle"),
+                VmError::CallEscaped => write!(f,"The called procedure escaped instead of returning"),
+                VmError::RejectedHandler(err) =>
+                    write!(f,"{} (the exception handler was rejected as it does not take one argument)",err),
+                VmError::Exited(status) => write!(f,"The program exited with status {}",status),
+            }
+        }
+    }
+
+    impl std::error::Error for VmError {}
+
 
     //VM
 
     use std::ops::{Index, IndexMut};
     use std::str::{Chars, from_utf8};
 
+    /// A rib of the heap, the three-field object every Scheme object that
+    /// is not a fixnum is made of.
     #[derive(Copy,Clone,PartialEq,Eq)]
-    struct Rib {
+    pub struct Rib {
         first: RibField,
         middle: RibField,
         last: RibField,
@@ -78,9 +270,9 @@ pub mod rvm {
 
     impl Display for Rib {
         fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-            write!(f,"[f:{},m:{},l:{}]",self.first.to_string(),
-                   self.middle.to_string(),
-                   self.last.to_string())
+            write!(f,"[f:{},m:{},l:{}]",self.first,
+                   self.middle,
+                   self.last)
         }
     }
 
@@ -88,168 +280,419 @@ pub mod rvm {
 
 
 
-    #[derive(Copy,Clone,Eq)]
-    enum RibField {
-        Rib(usize),
-        Number(i32),
-    }
+    /// A Scheme value: a reference to a rib of the heap, or a fixnum. It is
+    /// a single word like in the C host: fixnums have their low bit set and
+    /// hold 63 bits, and references hold the index of the rib shifted left.
+    #[derive(Copy,Clone,PartialEq,Eq)]
+    pub struct RibField(u64);
+
+    /// The smallest fixnum, smaller integers are bignums.
+    pub const MIN_FIXNUM: i64 = i64::MIN >> 1;
+    /// The largest fixnum, larger integers are bignums.
+    pub const MAX_FIXNUM: i64 = i64::MAX >> 1;
 
     impl Display for RibField {
         fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-            match *self {
-                RibField::Rib(ref inner) => write!(f,"r{}",*inner),
-                RibField::Number(ref n) => write!(f,"n{}",*n),
+            match self.as_rib() {
+                Some(r) => write!(f,"r{}",r),
+                None => write!(f,"n{}",self.as_number_unchecked()),
             }
         }
     }
 
-    impl PartialEq for RibField {
-        fn eq(&self, other: &Self) -> bool {
-            match self {
-                RibField::Rib(ref inner) =>
-                    match other {
-                        RibField::Rib(ref other_inner) =>
-                            inner == other_inner,
-                        RibField::Number(_) => false,
-                    },
-                RibField::Number(ref n) =>
-                    match other {
-                        RibField::Rib(_) => false,
-                        RibField::Number(ref other_n) => other_n == n,
-                    }
+    impl std::fmt::Debug for RibField {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            match self.as_rib() {
+                Some(r) => write!(f,"Rib({})",r),
+                None => write!(f,"Number({})",self.as_number_unchecked()),
             }
         }
     }
 
-    impl PartialOrd for RibField {
-        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-            if self == other {Some(Equal)} else {
-                match self {
-                    RibField::Rib(_) => {
-                        Option::None
-                    },
-                    RibField::Number(ref n) => {
-                        match other {
-                            RibField::Rib(_) => Option::None,
-                            RibField::Number(ref other_n) => {
-                                n.partial_cmp(other_n)
-                            },
-                        }
-                    },
-                }
+    impl RibField {
+        /// A reference to the rib at `index` in the heap.
+        pub const fn rib(index: usize) -> RibField {
+            RibField((index as u64) << 1)
+        }
+
+        // The fixnum n, which must be between MIN_FIXNUM and MAX_FIXNUM.
+        // Embedders use fixnum, which checks, or ToScheme, which makes a
+        // bignum of larger integers.
+        pub(crate) const fn number(n: i64) -> RibField {
+            debug_assert!(MIN_FIXNUM <= n && n <= MAX_FIXNUM, "fixnum out of range");
+            RibField((n as u64) << 1 | 1)
+        }
+
+        /// The fixnum `n`, or None if it is out of range.
+        pub fn fixnum(n: i64) -> Option<RibField> {
+            if (MIN_FIXNUM..=MAX_FIXNUM).contains(&n) { Some(RibField::number(n)) } else { None }
+        }
+
+        #[inline]
+        pub fn is_rib(&self) -> bool {
+            self.0 & 1 == 0
+        }
+
+        /// The index of the rib referred to, None for a fixnum.
+        #[inline]
+        pub fn as_rib(&self) -> Option<usize> {
+            if self.is_rib() { Some((self.0 >> 1) as usize) } else { None }
+        }
+
+        /// The value of the fixnum, None for a rib reference.
+        #[inline]
+        pub fn as_number(&self) -> Option<i64> {
+            if self.is_rib() { None } else { Some(self.as_number_unchecked()) }
+        }
+
+        #[inline]
+        fn as_number_unchecked(&self) -> i64 {
+            self.0 as i64 >> 1
+        }
+
+        fn get_rib(&self, holder: &mut RibHeap) -> Result<Rib, VmError> {
+            Ok(holder.get(&self.get_rib_ref()?))
+        }
+
+        #[inline]
+        fn get_number(&self) -> Result<i64, VmError> {
+            match self.as_number() {
+                Some(n) => Ok(n),
+                None => Err(VmError::TypeError(format!("Expected a number but got the rib index {}",self.0 >> 1))),
             }
+        }
 
+        #[inline]
+        fn get_rib_ref(&self) -> Result<usize, VmError> {
+            match self.as_rib() {
+                Some(r) => Ok(r),
+                None => Err(VmError::TypeError(format!("Expected a rib reference but got the number {}",self.as_number_unchecked()))),
+            }
         }
+
     }
 
-    impl Add for RibField{
-        type Output = Option<RibField>;
 
-        fn add(self, rhs: Self) -> Self::Output {
-            match self {
-                RibField::Number(ref n) => {
-                    match rhs {
-                        RibField::Number(ref m) => {
-                            Some(RibField::Number(n + m))
-                        },
-                        _ => Option::None,
-                    }
-                },
-                _ => Option::None,
-            }
+
+    /// The heap of ribs of a VM, where every Scheme object that is not a
+    /// fixnum lives. Host primitives receive it to inspect their arguments
+    /// and build their result.
+    ///
+    /// The garbage collector moves the ribs it keeps, so a `RibField` held
+    /// by Rust code is only valid until the next collection, which can
+    /// happen after any instruction. Values that must outlive that are
+    /// rooted with `root` and retrieved with `rooted`.
+    ///
+    /// Rust values are given to Scheme code as foreign objects, ribs that
+    /// refer to the value in a table of the heap. The value is dropped when
+    /// the garbage collector finds its foreign object unreachable.
+    pub struct RibHeap {
+        heap:Vec<Rib>,
+        old_len:usize, // the ribs before are the old generation, the others the nursery
+        remembered:Vec<usize>, // old ribs written with references to the nursery
+        collection:Option<Replication>, // the incremental major collection in progress
+        code:Code,
+        roots:Vec<Option<RibField>>,
+        foreign:Vec<Option<Foreign>>,
+        max_size:usize, // set by Vm::set_max_heap_size
+        exhausted:bool, // a rib was pushed past max_size
+    }
+
+    struct Foreign {
+        value: Box<dyn Any>,
+        type_name: &'static str,
+        old: bool, // its foreign object is in the old generation
+    }
+
+    /// A value rooted in a `RibHeap`, which the garbage collector keeps alive
+    /// and updates when it moves it. The value is released with `unroot`.
+    #[derive(Debug)]
+    pub struct Handle(usize);
+
+    // The ribs with weak fields found by a collection, as indices in the new
+    // heap. Their weak fields still refer to the old heap until the
+    // collection settles them.
+    #[derive(Default)]
+    struct WeakRefs {
+        weak_boxes: Vec<usize>,
+        ephemerons: Vec<usize>,
+        guarded: Vec<usize>,
+    }
+
+    // The ribs copied by a collection, numbered from base. A major collection
+    // copies the whole heap, a minor collection only the nursery and leaves
+    // the old generation where it is.
+    struct ToSpace {
+        base: usize,
+        ribs: Vec<Rib>,
+    }
+
+    impl ToSpace {
+        fn len(&self) -> usize {
+            self.base + self.ribs.len()
+        }
+
+        fn push(&mut self, rib: Rib) {
+            self.ribs.push(rib);
+        }
+    }
+
+    impl Index<usize> for ToSpace {
+        type Output = Rib;
+
+        fn index(&self, index: usize) -> &Self::Output {
+            &self.ribs[index - self.base]
+        }
+    }
+
+    impl IndexMut<usize> for ToSpace {
+        fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+            &mut self.ribs[index - self.base]
         }
     }
 
-    impl Sub for RibField{
-        type Output = Option<RibField>;
+    // Number of ribs allocated between two minor collections
+    const NURSERY_SIZE: usize = 1 << 16;
+
+    // Number of ribs copied by the incremental collector at each step
+    const COPY_PER_STEP: usize = 32;
+
+    // The index of the copy of a rib that was not copied yet
+    const NOT_COPIED: usize = usize::MAX;
+
+    // An incremental major collection. The reachable ribs of the old
+    // generation are copied to a new heap a few at a time while the program
+    // keeps using them, then the new heap replaces the heap in one short
+    // pause. A rib is copied with its current fields, and copied again when
+    // it is written afterwards: the program never sees the new heap, so
+    // that only writes need a barrier.
+    struct Replication {
+        new_heap: Vec<Rib>,
+        forward: Vec<usize>, // the index in new_heap of the copy of each rib
+        origin: Vec<usize>, // the index in the heap of each copy
+        scan: usize,
+        written: Vec<usize>, // ribs to copy again
+        weak: WeakRefs,
+        foreign: Vec<usize>, // the indices of the foreign objects copied
+    }
 
-        fn sub(self, rhs: Self) -> Self::Output {
-            match self {
-                RibField::Number(ref n) => {
-                    match rhs {
-                        RibField::Number(ref m) => {
-                            Some(RibField::Number(n - m))
-                        },
-                        _ => Option::None,
-                    }
-                },
-                _ => Option::None,
+    // The methods of Replication are given the old generation as heap: the
+    // ribs that refer to the nursery are copied again after the next minor
+    // collection, which moves the nursery to the old generation.
+    impl Replication {
+        fn new(heap: &[Rib]) -> Self {
+            let mut collection = Replication {
+                new_heap: Vec::with_capacity(heap.len()),
+                forward: vec![NOT_COPIED; heap.len()],
+                origin: Vec::with_capacity(heap.len()),
+                scan: 0,
+                written: Vec::new(),
+                weak: WeakRefs::default(),
+                foreign: Vec::new(),
+            };
+            // #f, #t and () keep their index
+            for i in 0..=NIL_REF {
+                collection.evacuate(i, heap);
             }
+            collection
         }
-    }
 
-    impl Mul for RibField{
-        type Output = Option<RibField>;
+        fn is_copied(&self, i: usize) -> bool {
+            self.forward.get(i).is_some_and(|&copy| copy != NOT_COPIED)
+        }
 
-        fn mul(self, rhs: Self) -> Self::Output {
-            match self {
-                RibField::Number(ref n) => {
-                    match rhs {
-                        RibField::Number(ref m) => {
-                            Some(RibField::Number(n * m))
-                        },
-                        _ => Option::None,
-                    }
-                },
-                _ => Option::None,
+        // Returns the index of the copy of rib i, which is made if needed
+        fn evacuate(&mut self, i: usize, heap: &[Rib]) -> usize {
+            if i >= self.forward.len() {
+                self.forward.resize(i + 1, NOT_COPIED);
+            }
+            if self.forward[i] == NOT_COPIED {
+                self.forward[i] = self.new_heap.len();
+                self.new_heap.push(heap[i]);
+                self.origin.push(i);
+            }
+            self.forward[i]
+        }
+
+        fn evacuate_roots(&mut self, registers: &[usize], roots: &[Option<RibField>], heap: &[Rib]) {
+            for &r in registers {
+                self.evacuate(r, heap);
+            }
+            for root in roots.iter().flatten() {
+                self.copy_field(root, heap);
             }
         }
-    }
 
-    impl Div for RibField {
-        type Output = Option<RibField>;
+        fn copy_field(&mut self, x: &RibField, heap: &[Rib]) -> RibField {
+            match x.as_rib() {
+                Some(i) => RibField::rib(self.evacuate(i, heap)),
+                None => *x,
+            }
+        }
 
-        fn div(self, rhs: Self) -> Self::Output {
-            match self {
-                RibField::Number(ref n) => {
-                    match rhs {
-                        RibField::Number(ref m) => {
-                            Some(RibField::Number(n / m))
-                        },
-                        _ => Option::None,
+        // Translates a strong field of a copy, false when it refers to the nursery
+        fn translate(&mut self, x: &mut RibField, heap: &[Rib]) -> bool {
+            match x.as_rib() {
+                Some(i) if i >= heap.len() => false,
+                _ => {
+                    *x = self.copy_field(x, heap);
+                    true
+                },
+            }
+        }
+
+        // Copies the current fields of the rib copied at index k. The weak
+        // fields are left to settle_weak_refs.
+        fn replicate(&mut self, k: usize, heap: &[Rib]) {
+            let i = self.origin[k];
+            let mut rib = heap[i];
+            let (weak_first, weak_middle) = match rib.last.as_number() {
+                Some(WEAK_BOX) => { self.weak.weak_boxes.push(k); (true, false) },
+                Some(EPHEMERON) => { self.weak.ephemerons.push(k); (true, true) },
+                Some(GUARDED) => { self.weak.guarded.push(k); (true, false) },
+                Some(FOREIGN) => {
+                    if let Some(index) = rib.middle.as_number() {
+                        self.foreign.push(index as usize);
                     }
+                    (false, false)
                 },
-                _ => Option::None,
+                _ => (false, false),
+            };
+            let mut complete = true;
+            if !weak_first {
+                complete &= self.translate(&mut rib.first, heap);
             }
+            if !weak_middle {
+                complete &= self.translate(&mut rib.middle, heap);
+            }
+            complete &= self.translate(&mut rib.last, heap);
+            if !complete {
+                self.written.push(i);
+            }
+            self.new_heap[k] = rib;
         }
-    }
 
-    impl RibField {
-        fn get_rib(&self, holder: &mut RibHeap) -> Rib {
-            match self {
-                RibField::Rib(ref inner) => holder.get(inner),
-                RibField::Number(n) =>
-                    panic!("Expected a rib reference but got the number {}",n),
+        // Copies a bounded number of ribs, true when they are all copied
+        fn step(&mut self, heap: &[Rib], mut count: usize) -> bool {
+            while count > 0 && self.scan < self.new_heap.len() {
+                self.replicate(self.scan, heap);
+                self.scan += 1;
+                count -= 1;
             }
+            self.scan == self.new_heap.len()
         }
 
-        fn get_number(&self) -> i32 {
-            match self {
-                RibField::Rib(ref inner) =>
-                    {panic!("Expected a number but got the rib index {}",inner)},
-                RibField::Number(ref n) => *n,
+        fn copy_written(&mut self, heap: &[Rib]) {
+            let mut written = std::mem::take(&mut self.written);
+            written.sort_unstable();
+            written.dedup();
+            for i in written {
+                let k = self.forward[i];
+                self.replicate(k, heap);
             }
         }
 
-        fn get_rib_ref(&self) -> usize {
-            match self {
-                RibField::Rib(ref inner) => *inner,
-                RibField::Number(ref n) =>
-                    panic!("Expected a rib reference but got the number {}",n),
+        fn complete(&mut self, heap: &[Rib]) {
+            loop {
+                self.step(heap, usize::MAX);
+                if self.written.is_empty() {
+                    break
+                }
+                self.copy_written(heap);
             }
         }
 
-    }
+        // The copy of x, None if it was not copied
+        fn forwarded(&self, x: &RibField) -> Option<RibField> {
+            match x.as_rib() {
+                Some(i) if self.is_copied(i) => Some(RibField::rib(self.forward[i])),
+                Some(_) => None,
+                None => Some(*x),
+            }
+        }
+
+        // Like RibHeap::settle_weak_refs, once the copy is complete. A rib
+        // may have been recorded more than once, or written since with
+        // another type.
+        fn settle_weak_refs(&mut self, heap: &[Rib]) {
+            loop {
+                self.copy_ephemeron_values(heap);
+                self.keep_weak(GUARDED);
+                if self.weak.guarded.is_empty() {
+                    break
+                }
+                self.resurrect_guarded(heap);
+            }
+            self.keep_weak(EPHEMERON);
+            for e in std::mem::take(&mut self.weak.ephemerons) {
+                self.new_heap[e].first = RibField::rib(FALSE_REF);
+                self.new_heap[e].middle = RibField::rib(FALSE_REF);
+            }
+            self.keep_weak(WEAK_BOX);
+            for b in std::mem::take(&mut self.weak.weak_boxes) {
+                let obj = heap[self.origin[b]].first;
+                self.new_heap[b].first = self.forwarded(&obj).unwrap_or(RibField::rib(FALSE_REF));
+            }
+        }
 
+        // Keeps each copy recorded with the weak type tag once, if it still
+        // has that type
+        fn keep_weak(&mut self, tag: i64) {
+            let recorded = match tag {
+                WEAK_BOX => &mut self.weak.weak_boxes,
+                EPHEMERON => &mut self.weak.ephemerons,
+                _ => &mut self.weak.guarded,
+            };
+            let new_heap = &self.new_heap;
+            recorded.sort_unstable();
+            recorded.dedup();
+            recorded.retain(|&k| new_heap[k].last == RibField::number(tag));
+        }
 
+        fn copy_ephemeron_values(&mut self, heap: &[Rib]) {
+            self.keep_weak(EPHEMERON);
+            let mut progress = true;
+            while progress {
+                progress = false;
+                for e in std::mem::take(&mut self.weak.ephemerons) {
+                    let entry = heap[self.origin[e]];
+                    match self.forwarded(&entry.first) {
+                        Some(key) => {
+                            let value = self.copy_field(&entry.middle, heap);
+                            self.complete(heap);
+                            self.new_heap[e].first = key;
+                            self.new_heap[e].middle = value;
+                            progress = true;
+                        },
+                        None => self.weak.ephemerons.push(e),
+                    }
+                }
+            }
+        }
 
-    struct RibHeap {
-        heap:Vec<Rib>,
+        fn resurrect_guarded(&mut self, heap: &[Rib]) {
+            let mut dead = Vec::new();
+            for g in std::mem::take(&mut self.weak.guarded) {
+                let obj = heap[self.origin[g]].first;
+                match self.forwarded(&obj) {
+                    Some(copy) => self.new_heap[g].first = copy,
+                    None => dead.push(g),
+                }
+            }
+            for g in dead {
+                let obj = heap[self.origin[g]].first;
+                let copy = self.copy_field(&obj, heap);
+                self.complete(heap);
+                self.new_heap[g].first = copy;
+                self.new_heap[g].last = RibField::number(PAIR);
+            }
+        }
     }
 
     impl RibHeap {
         fn push_rib(&mut self, data:Rib) -> usize {
             let index = self.heap.len(); // len() is how many ribs are before the pushed one
+            self.exhausted |= index >= self.max_size;
             self.heap.push(data);
             index
         }
@@ -258,157 +701,539 @@ pub mod rvm {
 
         fn with_capacity(capacity: usize) -> Self {
             RibHeap{
-                heap: Vec::with_capacity(capacity)
+                heap: Vec::with_capacity(capacity),
+                old_len: 0,
+                remembered: Vec::new(),
+                collection: None,
+                code: Code {
+                    decoded: Vec::new(),
+                    instrs: Vec::new(),
+                    enabled: true,
+                    native: Vec::new(),
+                    native_written: false,
+                },
+                roots: Vec::new(),
+                foreign: Vec::new(),
+                max_size: usize::MAX,
+                exhausted: false,
             }
         }
 
+        // Every write to an existing rib goes through set, whose write barrier
+        // remembers the old ribs that refer to the nursery: they are roots of
+        // the next minor collection.
+        // The ribs already copied by an incremental collection are also
+        // remembered, to be copied again.
         fn set(&mut self, i:&usize, r:Rib) {
+            if *i < self.old_len && [r.first, r.middle, r.last].iter().any(|x| self.is_young(x)) {
+                self.remembered.push(*i);
+            }
+            if let Some(ref mut collection) = self.collection {
+                if collection.is_copied(*i) {
+                    collection.written.push(*i);
+                }
+            }
+            if self.code.is_decoded(*i) {
+                self.code.forget();
+            }
+            if self.code.is_native(*i) {
+                self.code.native.clear();
+                self.code.native_written = true;
+            }
             self[*i] = r;
         }
 
+        // The operation at index pc, decoded once in the old generation. The
+        // operations that refer to the nursery are not kept, as a minor
+        // collection moves what they refer to.
+        fn instr(&mut self, pc: usize) -> Result<Instr, VmError> {
+            if let Some(&d) = self.code.decoded.get(pc) {
+                if d != 0 {
+                    return Ok(self.code.instrs[d as usize - 1])
+                }
+            }
+            let rib = self[pc];
+            let instr = Instr::decode(&rib)?;
+            if self.code.enabled && pc < self.old_len &&
+                ![rib.first, rib.middle, rib.last].iter().any(|x| self.is_young(x)) {
+                if pc >= self.code.decoded.len() {
+                    self.code.decoded.resize(self.old_len, 0);
+                }
+                self.code.instrs.push(instr);
+                self.code.decoded[pc] = self.code.instrs.len() as u32;
+            }
+            Ok(instr)
+        }
+
+        fn is_young(&self, x: &RibField) -> bool {
+            x.as_rib().is_some_and(|r| r >= self.old_len)
+        }
+
+        #[inline]
+        fn nursery_len(&self) -> usize {
+            self.heap.len() - self.old_len
+        }
+
+        #[inline]
         fn get(&mut self, i:&usize) -> Rib {
             self[*i]
         }
 
-        fn garbage_collect(&mut self, stack: &mut usize, pc: &mut usize,symtbl: &mut usize) -> usize {
-
-            let broken_rib = RibField::Rib(self.heap.len()+1);
+        /// Allocates a Scheme string holding `s`.
+        pub fn make_string(&mut self, s: &str) -> RibField {
+            str2scm(s, self)
+        }
 
+        /// Returns the contents of the Scheme string `s`.
+        pub fn get_string(&mut self, s: &RibField) -> Result<String, VmError> {
+            scm2str(s, self)
+        }
 
-            //let mut old_heap_record = self.heap.clone();//DEBUG
+        /// Keeps `value` alive across garbage collections until the
+        /// returned handle is given to `unroot`.
+        pub fn root(&mut self, value: RibField) -> Handle {
+            match self.roots.iter().position(Option::is_none) {
+                Some(i) => {
+                    self.roots[i] = Some(value);
+                    Handle(i)
+                },
+                None => {
+                    self.roots.push(Some(value));
+                    Handle(self.roots.len() - 1)
+                },
+            }
+        }
 
+        /// Allocates a foreign object holding `value`.
+        pub fn make_foreign<T: Any>(&mut self, value: T) -> RibField {
+            let foreign = Foreign { value: Box::new(value), type_name: type_name::<T>(), old: false };
+            let index = match self.foreign.iter().position(Option::is_none) {
+                Some(i) => {
+                    self.foreign[i] = Some(foreign);
+                    i
+                },
+                None => {
+                    self.foreign.push(Some(foreign));
+                    self.foreign.len() - 1
+                },
+            };
+            RibField::rib(self.push_rib(make_data_rib(RibField::number(0),
+                                                      RibField::number(index as i64),
+                                                      FOREIGN)))
+        }
 
-            let mut new_heap = Vec::with_capacity(self.heap.len());
+        /// Returns the value held by the foreign object `x`, which must be
+        /// of type `T`.
+        pub fn get_foreign<T: Any>(&mut self, x: &RibField) -> Result<&mut T, VmError> {
+            let expected = || VmError::TypeError(format!("Expected a foreign {}", type_name::<T>()));
+            if !is_foreign(x, self) {
+                return Err(expected())
+            }
+            let index = x.get_rib(self)?.middle.get_number()?;
+            match self.foreign.get_mut(index as usize) {
+                Some(&mut Some(ref mut foreign)) => {
+                    let found = foreign.type_name;
+                    foreign.value.downcast_mut::<T>().ok_or_else(|| VmError::TypeError(
+                        format!("Expected a foreign {} but got a foreign {}", type_name::<T>(), found)))
+                },
+                _ => Err(expected()),
+            }
+        }
 
-            new_heap.push(FALSE); //FALSE
-            let broken_false = make_data_rib(broken_rib,RibField::Rib(0),SPECIAL);
-            self.set(&0,broken_false);
+        /// Returns the current value of a rooted value, or
+        /// `VmError::UnknownHandle` if `handle` comes from another heap.
+        #[inline]
+        pub fn rooted(&self, handle: &Handle) -> Result<RibField, VmError> {
+            self.roots.get(handle.0).and_then(|root| *root).ok_or(VmError::UnknownHandle)
+        }
 
-            new_heap.push(TRUE); //TRUE
-            let broken_true = make_data_rib(broken_rib,RibField::Rib(1),SPECIAL);
-            self.set(&1,broken_true);
+        /// Releases a rooted value, which the garbage collector may then
+        /// reclaim, and returns it. Fails like `rooted`.
+        pub fn unroot(&mut self, handle: Handle) -> Result<RibField, VmError> {
+            self.roots.get_mut(handle.0).and_then(Option::take).ok_or(VmError::UnknownHandle)
+        }
 
-            new_heap.push(NIL); //NIL
-            let broken_nil = make_data_rib(broken_rib,RibField::Rib(2),SPECIAL);
-            self.set(&2, broken_nil);
+        // A major collection, which copies every reachable rib. An incremental
+        // collection in progress is abandoned.
+        fn garbage_collect(&mut self, stack: &mut usize, pc: &mut usize,symtbl: &mut usize,
+                           handler: &mut usize) -> usize {
+            self.collection = None;
+            self.collect(0, stack, pc, symtbl, handler)
+        }
 
-            self.stop_and_copy(symtbl, &mut new_heap);
+        // Starts an incremental major collection, which step_collect carries
+        // on, or only collects the nursery when one is already in progress
+        fn start_incremental_collect(&mut self, stack: &mut usize, pc: &mut usize,symtbl: &mut usize,
+                                     handler: &mut usize) {
+            let in_progress = self.collection.is_some();
+            self.minor_collect(stack, pc, symtbl, handler);
+            if !in_progress {
+                let mut collection = Replication::new(&self.heap);
+                collection.evacuate_roots(&[*stack, *pc, *symtbl, *handler], &self.roots, &self.heap);
+                self.collection = Some(collection);
+            }
+        }
 
-            self.stop_and_copy(pc, &mut new_heap);
+        // Copies a bounded number of ribs for the incremental collection in
+        // progress and tells whether it is ready to be finished
+        fn step_collect(&mut self) -> bool {
+            match self.collection {
+                Some(ref mut collection) => collection.step(&self.heap[..self.old_len], COPY_PER_STEP),
+                None => false,
+            }
+        }
 
-            self.stop_and_copy(stack, &mut new_heap);
+        // Copies what the incremental collection has left, then replaces the
+        // heap with the new one
+        fn finish_incremental_collect(&mut self, stack: &mut usize, pc: &mut usize,symtbl: &mut usize,
+                                      handler: &mut usize) -> usize {
+            self.minor_collect(stack, pc, symtbl, handler);
+            let mut collection = match self.collection.take() {
+                Some(collection) => collection,
+                None => return self.heap.len(),
+            };
+            collection.evacuate_roots(&[*stack, *pc, *symtbl, *handler], &self.roots, &self.heap);
+            collection.complete(&self.heap);
+            collection.settle_weak_refs(&self.heap);
+
+            let forward = &collection.forward;
+            *stack = forward[*stack];
+            *pc = forward[*pc];
+            *symtbl = forward[*symtbl];
+            *handler = forward[*handler];
+            for root in self.roots.iter_mut().flatten() {
+                if let Some(r) = root.as_rib() {
+                    *root = RibField::rib(forward[r]);
+                }
+            }
 
-            self.heap = new_heap;
+            let mut reachable = vec![false; self.foreign.len()];
+            for &index in &collection.foreign {
+                if let Some(r) = reachable.get_mut(index) {
+                    *r = true;
+                }
+            }
+            self.heap = collection.new_heap;
+            self.old_len = self.heap.len();
+            self.remembered.clear();
+            self.code.moved();
+            self.drop_foreign(reachable, true);
             self.heap.len()
         }
 
-        fn stop_and_copy(&mut self, root: &mut usize, new_heap: &mut Vec<Rib>) {
-
-            let broken_rib = RibField::Rib(self.heap.len() + 1);
+        // A minor collection, which only copies the reachable ribs of the
+        // nursery: the old generation is assumed to be reachable and the
+        // remembered ribs are the only ones of it that may refer to the
+        // nursery. The ribs copied are promoted to the old generation.
+        fn minor_collect(&mut self, stack: &mut usize, pc: &mut usize,symtbl: &mut usize,
+                         handler: &mut usize) -> usize {
+            let old_len = self.old_len;
+            let len = self.collect(old_len, stack, pc, symtbl, handler);
+
+            // The ribs of the incremental collection in progress that referred
+            // to the nursery can now be copied again, and the registers may
+            // refer to ribs promoted
+            if let Some(ref mut collection) = self.collection {
+                collection.copy_written(&self.heap);
+                collection.evacuate_roots(&[*stack, *pc, *symtbl, *handler], &self.roots, &self.heap);
+            }
+            len
+        }
 
-            // FR: Si le Rib référencé par root est déjà dans le new_heap alors, par récursion,
-            // les Ribs auxquels il est connexe sont déjà copiés et il n'est pas nécessaire de poursuivre le copiage.
-            // ENG: If the Rib referenced by root is already copied then, by recursion, the Ribs to which it is
-            // connected are already copied and the copying doesn't need to take place.
+        fn collect(&mut self, base: usize, stack: &mut usize, pc: &mut usize,symtbl: &mut usize,
+                   handler: &mut usize) -> usize {
 
-            if self.get(root).first == broken_rib
-            {
-                let new_root = self.get(root).middle;
-                *root = new_root.get_rib_ref();
-                return;
-            }
+            let broken_rib = RibField::rib(self.heap.len()+1);
 
-            // FR: Initialisation des pointeurs scan et copy. ENG: Initialization of the scan and copy pointers.
 
-            let mut scan: usize = new_heap.len();
-            let mut copy: usize = new_heap.len();
+            //let mut old_heap_record = self.heap.clone();//DEBUG
 
-            let mut old_start = self.get(root);
-            let mut copied_rib = old_start.clone();
 
-            // FR: Le marqueur va être écrit dans le champ first, l'adresse de sa copie dans le champ middle
-            // ENG: The mark will be written in the first field, the address of its copy in the middle field
-            old_start.first = broken_rib;
-            old_start.middle = RibField::Rib(copy);
-            self.set(root,old_start);
+            let mut new_heap = ToSpace { base, ribs: Vec::with_capacity(self.heap.len() - base) };
 
-            new_heap.push(copied_rib);
-            copy += 1;
+            if base == 0 {
+                new_heap.push(FALSE); //FALSE
+                let broken_false = make_data_rib(broken_rib,RibField::rib(0),SPECIAL);
+                self[0] = broken_false;
 
-            // FR: Mise à jour du pointeur root. ENG: Updating the root pointer.
-            *root = scan;
+                new_heap.push(TRUE); //TRUE
+                let broken_true = make_data_rib(broken_rib,RibField::rib(1),SPECIAL);
+                self[1] = broken_true;
 
-            while scan != copy
-            {
-                copied_rib = new_heap[scan];
+                new_heap.push(NIL); //NIL
+                let broken_nil = make_data_rib(broken_rib,RibField::rib(2),SPECIAL);
+                self[2] = broken_nil;
+            }
 
-                let mut is_changed = false;
-                if is_rib(&copied_rib.first)
-                {
-                    is_changed = true;
-                    let mut past_rib = copied_rib.first.get_rib(self);
-                    if past_rib.first == broken_rib
-                    {
-                        copied_rib.first = past_rib.middle;
-                    } else
-                    {
-                        let past_rib_ref = copied_rib.first.get_rib_ref();
+            let mut weak = WeakRefs::default();
 
-                        copied_rib.first = RibField::Rib(copy);
+            self.stop_and_copy(symtbl, &mut new_heap, &mut weak);
 
-                        new_heap.push(past_rib.clone());
+            self.stop_and_copy(pc, &mut new_heap, &mut weak);
 
-                        past_rib.first = broken_rib;
-                        past_rib.middle = RibField::Rib(copy);
+            self.stop_and_copy(stack, &mut new_heap, &mut weak);
 
-                        copy += 1;
+            self.stop_and_copy(handler, &mut new_heap, &mut weak);
 
-                        self.set(&past_rib_ref,past_rib);
-                    }
+            let mut roots = std::mem::take(&mut self.roots);
+            for root in roots.iter_mut().flatten() {
+                self.copy_field(root, &mut new_heap, &mut weak);
+            }
+            self.roots = roots;
+
+            // The weak references of the remembered ribs are strong until the
+            // next major collection. A rib is remembered for each write, but
+            // its fields must be copied once.
+            let mut remembered = std::mem::take(&mut self.remembered);
+            remembered.sort_unstable();
+            remembered.dedup();
+            for i in remembered {
+                if base > 0 {
+                    let mut rib = self[i];
+                    self.copy_field(&mut rib.first, &mut new_heap, &mut weak);
+                    self.copy_field(&mut rib.middle, &mut new_heap, &mut weak);
+                    self.copy_field(&mut rib.last, &mut new_heap, &mut weak);
+                    self[i] = rib;
                 }
+            }
 
-                if is_rib(&copied_rib.middle)
-                {
-                    is_changed = true;
-                    let mut past_rib = copied_rib.middle.get_rib(self);
-                    if past_rib.first == broken_rib
-                    {
-                        copied_rib.middle = past_rib.middle;
-                    } else
-                    {
-                        let past_rib_ref = copied_rib.middle.get_rib_ref();
-                        copied_rib.middle = RibField::Rib(copy);
+            // Guarded objects are resurrected after everything else reachable
+            // is copied, which includes the values of the ephemerons with a
+            // live key. Weak boxes are settled last and keep resurrected objects.
+            loop {
+                self.copy_ephemeron_values(&mut new_heap, &mut weak);
+                if weak.guarded.is_empty() {
+                    break
+                }
+                self.resurrect_guarded(&mut new_heap, &mut weak);
+            }
+            for e in weak.ephemerons {
+                new_heap[e].first = RibField::rib(FALSE_REF);
+                new_heap[e].middle = RibField::rib(FALSE_REF);
+            }
+            for b in weak.weak_boxes {
+                new_heap[b].first = self.forwarded(&new_heap[b].first, &new_heap).unwrap_or(RibField::rib(FALSE_REF));
+            }
 
-                        new_heap.push(past_rib.clone());
+            self.heap.truncate(base);
+            self.heap.append(&mut new_heap.ribs);
+            self.old_len = self.heap.len();
+            if base == 0 {
+                self.code.moved();
+            }
+            self.drop_unreachable_foreign(base);
+            self.heap.len()
+        }
+
+        // Drops the values of the foreign objects the last collection did not
+        // copy. The old ones are reachable when the collection was minor.
+        fn drop_unreachable_foreign(&mut self, base: usize) {
+            let collected = |foreign: &Foreign| base == 0 || !foreign.old;
+            if !self.foreign.iter().flatten().any(collected) {
+                return
+            }
+            let mut reachable = vec![false; self.foreign.len()];
+            for rib in &self.heap[base..] {
+                if rib.last == RibField::number(FOREIGN) {
+                    if let Some(index) = rib.middle.as_number() {
+                        if let Some(r) = reachable.get_mut(index as usize) {
+                            *r = true;
+                        }
+                    }
+                }
+            }
+            self.drop_foreign(reachable, base == 0);
+        }
+
+        // Drops the values of the foreign objects that are not reachable,
+        // only the young ones after a minor collection
+        fn drop_foreign(&mut self, reachable: Vec<bool>, major: bool) {
+            for (foreign, reachable) in self.foreign.iter_mut().zip(reachable) {
+                if reachable {
+                    if let Some(ref mut foreign) = *foreign {
+                        foreign.old = true;
+                    }
+                } else if foreign.as_ref().is_some_and(|foreign| major || !foreign.old) {
+                    *foreign = None;
+                }
+            }
+        }
+
+        // The new location of x during a collection, None if it was not copied
+        fn forwarded(&self, x: &RibField, new_heap: &ToSpace) -> Option<RibField> {
+            let broken_rib = RibField::rib(self.heap.len() + 1);
+            match x.as_rib() {
+                Some(r) if r < new_heap.base => Some(*x),
+                Some(r) if self[r].first == broken_rib => Some(self[r].middle),
+                Some(_) => None,
+                None => Some(*x),
+            }
+        }
+
+        fn copy_field(&mut self, x: &mut RibField, new_heap: &mut ToSpace, weak: &mut WeakRefs) {
+            if let Some(mut r) = x.as_rib() {
+                self.stop_and_copy(&mut r, new_heap, weak);
+                *x = RibField::rib(r);
+            }
+        }
+
+        // Copies the values of the ephemerons whose key was copied, until no
+        // more keys become reachable that way
+        fn copy_ephemeron_values(&mut self, new_heap: &mut ToSpace, weak: &mut WeakRefs) {
+            let mut progress = true;
+            while progress {
+                progress = false;
+                for e in std::mem::take(&mut weak.ephemerons) {
+                    match self.forwarded(&new_heap[e].first, new_heap) {
+                        Some(key) => {
+                            let mut value = new_heap[e].middle;
+                            self.copy_field(&mut value, new_heap, weak);
+                            new_heap[e].first = key;
+                            new_heap[e].middle = value;
+                            progress = true;
+                        },
+                        None => weak.ephemerons.push(e),
+                    }
+                }
+            }
+        }
+
+        // Copies the objects of the guarded entries that are not reachable
+        // otherwise, and turns these entries into pairs for their guardian
+        fn resurrect_guarded(&mut self, new_heap: &mut ToSpace, weak: &mut WeakRefs) {
+            let mut dead = Vec::new();
+            for g in std::mem::take(&mut weak.guarded) {
+                match self.forwarded(&new_heap[g].first, new_heap) {
+                    Some(obj) => new_heap[g].first = obj,
+                    None => dead.push(g),
+                }
+            }
+            for g in dead {
+                let mut obj = new_heap[g].first;
+                self.copy_field(&mut obj, new_heap, weak);
+                new_heap[g].first = obj;
+                new_heap[g].last = RibField::number(PAIR);
+            }
+        }
+
+        fn stop_and_copy(&mut self, root: &mut usize, new_heap: &mut ToSpace, weak: &mut WeakRefs) {
+
+            let broken_rib = RibField::rib(self.heap.len() + 1);
+
+            // The ribs before the base of the new heap stay where they are
+            let base = new_heap.base;
+            if *root < base {
+                return;
+            }
+            let moving = |x: RibField| x.as_rib().filter(|&r| r >= base);
+
+            // FR: Si le Rib référencé par root est déjà dans le new_heap alors, par récursion,
+            // les Ribs auxquels il est connexe sont déjà copiés et il n'est pas nécessaire de poursuivre le copiage.
+            // ENG: If the Rib referenced by root is already copied then, by recursion, the Ribs to which it is
+            // connected are already copied and the copying doesn't need to take place.
+
+            let root_rib = self.get(root);
+            if root_rib.first == broken_rib
+            {
+                if let Some(new_root) = root_rib.middle.as_rib() {
+                    *root = new_root;
+                }
+                return;
+            }
+
+            // FR: Initialisation des pointeurs scan et copy. ENG: Initialization of the scan and copy pointers.
+
+            let mut scan: usize = new_heap.len();
+            let mut copy: usize = new_heap.len();
+
+            let mut old_start = self.get(root);
+            let mut copied_rib = old_start;
+
+            // FR: Le marqueur va être écrit dans le champ first, l'adresse de sa copie dans le champ middle
+            // ENG: The mark will be written in the first field, the address of its copy in the middle field
+            old_start.first = broken_rib;
+            old_start.middle = RibField::rib(copy);
+            self[*root] = old_start;
+
+            new_heap.push(copied_rib);
+            copy += 1;
+
+            // FR: Mise à jour du pointeur root. ENG: Updating the root pointer.
+            *root = scan;
+
+            while scan != copy
+            {
+                copied_rib = new_heap[scan];
+
+                // The weak fields are left to garbage_collect
+                let (weak_first, weak_middle) = match copied_rib.last.as_number() {
+                    Some(WEAK_BOX) => { weak.weak_boxes.push(scan); (true, false) },
+                    Some(EPHEMERON) => { weak.ephemerons.push(scan); (true, true) },
+                    Some(GUARDED) => { weak.guarded.push(scan); (true, false) },
+                    _ => (false, false),
+                };
+
+                let mut is_changed = false;
+                if let (false, Some(past_rib_ref)) = (weak_first, moving(copied_rib.first))
+                {
+                    is_changed = true;
+                    let mut past_rib = self.get(&past_rib_ref);
+                    if past_rib.first == broken_rib
+                    {
+                        copied_rib.first = past_rib.middle;
+                    } else {
+
+                        copied_rib.first = RibField::rib(copy);
+
+                        new_heap.push(past_rib);
 
                         past_rib.first = broken_rib;
-                        past_rib.middle = RibField::Rib(copy);
+                        past_rib.middle = RibField::rib(copy);
 
                         copy += 1;
 
-                        self.set(&past_rib_ref,past_rib);
+                        self[past_rib_ref] = past_rib;
                     }
                 }
 
-                if is_rib(&copied_rib.last)
+                if let (false, Some(past_rib_ref)) = (weak_middle, moving(copied_rib.middle))
                 {
                     is_changed = true;
-                    let mut past_rib = copied_rib.last.get_rib(self);
+                    let mut past_rib = self.get(&past_rib_ref);
                     if past_rib.first == broken_rib
                     {
-                        copied_rib.last = past_rib.middle;
-                    } else
+                        copied_rib.middle = past_rib.middle;
+                    } else {
+                        copied_rib.middle = RibField::rib(copy);
+
+                        new_heap.push(past_rib);
+
+                        past_rib.first = broken_rib;
+                        past_rib.middle = RibField::rib(copy);
+
+                        copy += 1;
+
+                        self[past_rib_ref] = past_rib;
+                    }
+                }
+
+                if let Some(past_rib_ref) = moving(copied_rib.last)
+                {
+                    is_changed = true;
+                    let mut past_rib = self.get(&past_rib_ref);
+                    if past_rib.first == broken_rib
                     {
-                        let past_rib_ref = copied_rib.last.get_rib_ref();
-                        copied_rib.last = RibField::Rib(copy);
+                        copied_rib.last = past_rib.middle;
+                    } else {
+                        copied_rib.last = RibField::rib(copy);
 
-                        new_heap.push(past_rib.clone());
+                        new_heap.push(past_rib);
 
                         past_rib.first = broken_rib;
-                        past_rib.middle = RibField::Rib(copy);
+                        past_rib.middle = RibField::rib(copy);
 
                         copy += 1;
-                        self.set(&past_rib_ref,past_rib);
+                        self[past_rib_ref] = past_rib;
                     }
                 }
 
@@ -450,12 +1275,14 @@ pub mod rvm {
     impl Index<usize> for RibHeap{
         type Output = Rib;
 
+        #[inline]
         fn index(&self, index: usize) -> &Self::Output {
             &self.heap[index]
         }
     }
 
     impl IndexMut<usize> for RibHeap {
+        #[inline]
         fn index_mut(&mut self, index: usize) -> &mut Self::Output {
             &mut self.heap[index]
         }
@@ -463,25 +1290,25 @@ pub mod rvm {
 
 
     const NIL: Rib = Rib {
-        first: RibField::Number(0),
-        middle: RibField::Number(0),
-        last: RibField::Number(SPECIAL),
+        first: RibField::number(0),
+        middle: RibField::number(0),
+        last: RibField::number(SPECIAL),
     };
 
     const NIL_REF:usize = 2;
 
 
     const TRUE: Rib = Rib {
-        first: RibField::Number(0),
-        middle: RibField::Number(0),
-        last: RibField::Number(SPECIAL),
+        first: RibField::number(0),
+        middle: RibField::number(0),
+        last: RibField::number(SPECIAL),
     };
     const TRUE_REF: usize = 1;
 
     const FALSE: Rib = Rib {
-        first: RibField::Number(0),
-        middle: RibField::Number(0),
-        last: RibField::Number(SPECIAL),
+        first: RibField::number(0),
+        middle: RibField::number(0),
+        last: RibField::number(SPECIAL),
     };
 
     const FALSE_REF: usize =0;
@@ -494,12 +1321,396 @@ pub mod rvm {
         }
     }
 
-    fn make_data_rib(first: RibField, middle: RibField, last: i32) -> Rib {
-        make_rib(first, middle, RibField::Number(last))
+    fn make_data_rib(first: RibField, middle: RibField, last: i64) -> Rib {
+        make_rib(first, middle, RibField::number(last))
+    }
+
+    fn make_op_rib(first: i64, middle: RibField, last: RibField) -> Rib {
+        make_rib(RibField::number(first), middle, last)
+    }
+
+
+    // Bignums
+//
+// Integers that do not fit in a fixnum are boxed in a rib. The digits are
+// in base 2^32, least significant first, and the sign is -1 or 1. A bignum
+// is always normalized: its value never fits in a fixnum (so it is never 0).
+// rib? is false for bignums, as for flonums, so that the libraries can keep
+// defining number? as (not (rib? obj)).
+
+    #[derive(Clone,PartialEq,Eq)]
+    struct BigInt {
+        negative: bool,
+        digits: Vec<u32>, // no leading (most significant) zero digit
+    }
+
+    impl BigInt {
+        fn from_i64(n: i64) -> BigInt {
+            let mut magnitude = n.unsigned_abs();
+            let mut digits = Vec::new();
+            while magnitude != 0 {
+                digits.push(magnitude as u32);
+                magnitude >>= 32;
+            }
+            BigInt { negative: n < 0, digits }
+        }
+
+        fn to_i64(&self) -> Option<i64> {
+            if self.digits.len() > 2 {
+                return None
+            }
+            let magnitude = self.digits.iter().rev()
+                .fold(0u64, |acc, &d| acc << 32 | d as u64);
+            if !self.negative {
+                if magnitude <= i64::MAX as u64 { Some(magnitude as i64) } else { None }
+            } else if magnitude <= i64::MIN.unsigned_abs() {
+                Some((magnitude as i64).wrapping_neg())
+            } else {
+                None
+            }
+        }
+
+        // Only for integral finite values
+        fn from_f64(f: f64) -> BigInt {
+            let mut magnitude = f.abs();
+            let mut digits = Vec::new();
+            while magnitude >= 1.0 {
+                digits.push((magnitude % 4294967296.0) as u32);
+                magnitude = (magnitude / 4294967296.0).floor();
+            }
+            BigInt { negative: f < 0.0, digits }.normalize()
+        }
+
+        fn to_f64(&self) -> f64 {
+            let magnitude = self.digits.iter().rev()
+                .fold(0.0, |acc, &d| acc * 4294967296.0 + d as f64);
+            if self.negative { -magnitude } else { magnitude }
+        }
+
+        fn normalize(mut self) -> BigInt {
+            while self.digits.last() == Some(&0) {
+                self.digits.pop();
+            }
+            if self.digits.is_empty() {
+                self.negative = false;
+            }
+            self
+        }
+
+        fn add(&self, other: &BigInt) -> BigInt {
+            if self.negative == other.negative {
+                BigInt { negative: self.negative, digits: add_digits(&self.digits, &other.digits) }
+            } else if cmp_digits(&self.digits, &other.digits) != Ordering::Less {
+                BigInt { negative: self.negative, digits: sub_digits(&self.digits, &other.digits) }
+                    .normalize()
+            } else {
+                BigInt { negative: other.negative, digits: sub_digits(&other.digits, &self.digits) }
+                    .normalize()
+            }
+        }
+
+        fn sub(&self, other: &BigInt) -> BigInt {
+            let negated = BigInt { negative: !other.negative, digits: other.digits.clone() };
+            self.add(&negated.normalize())
+        }
+
+        fn mul(&self, other: &BigInt) -> BigInt {
+            let mut digits = vec![0u32; self.digits.len() + other.digits.len()];
+            for (i, &a) in self.digits.iter().enumerate() {
+                let mut carry = 0u64;
+                for (j, &b) in other.digits.iter().enumerate() {
+                    let t = a as u64 * b as u64 + digits[i + j] as u64 + carry;
+                    digits[i + j] = t as u32;
+                    carry = t >> 32;
+                }
+                digits[i + other.digits.len()] = carry as u32;
+            }
+            BigInt { negative: self.negative != other.negative, digits }.normalize()
+        }
+
+        // Truncates toward zero, like the quotient primitive. The divisor
+        // must not be zero.
+        fn quotient(&self, other: &BigInt) -> BigInt {
+            let digits = if other.digits.len() == 1 {
+                div_digit(&self.digits, other.digits[0]).0
+            } else {
+                let mut digits = vec![0u32; self.digits.len()];
+                let mut remainder: Vec<u32> = Vec::new();
+                for i in (0..self.digits.len() * 32).rev() {
+                    // remainder = remainder * 2 + bit i of the dividend
+                    let mut carry = self.digits[i / 32] >> (i % 32) & 1;
+                    for d in remainder.iter_mut() {
+                        let next_carry = *d >> 31;
+                        *d = *d << 1 | carry;
+                        carry = next_carry;
+                    }
+                    if carry != 0 {
+                        remainder.push(carry);
+                    }
+                    if cmp_digits(&remainder, &other.digits) != Ordering::Less {
+                        remainder = sub_digits(&remainder, &other.digits);
+                        while remainder.last() == Some(&0) {
+                            remainder.pop();
+                        }
+                        digits[i / 32] |= 1 << (i % 32);
+                    }
+                }
+                digits
+            };
+            BigInt { negative: self.negative != other.negative, digits }.normalize()
+        }
+    }
+
+    impl Ord for BigInt {
+        fn cmp(&self, other: &Self) -> Ordering {
+            match (self.negative, other.negative) {
+                (false, true) => Ordering::Greater,
+                (true, false) => Ordering::Less,
+                (false, false) => cmp_digits(&self.digits, &other.digits),
+                (true, true) => cmp_digits(&other.digits, &self.digits),
+            }
+        }
+    }
+
+    impl PartialOrd for BigInt {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Display for BigInt {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            // Peel off groups of 9 decimal digits, least significant first
+            let mut groups = Vec::new();
+            let mut digits = self.digits.clone();
+            while !digits.is_empty() {
+                let (quotient, remainder) = div_digit(&digits, 1_000_000_000);
+                groups.push(remainder);
+                digits = quotient;
+                while digits.last() == Some(&0) {
+                    digits.pop();
+                }
+            }
+            if self.negative {
+                write!(f, "-")?;
+            }
+            match groups.pop() {
+                Some(group) => write!(f, "{}", group)?,
+                None => write!(f, "0")?,
+            }
+            for group in groups.iter().rev() {
+                write!(f, "{:09}", group)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn cmp_digits(a: &[u32], b: &[u32]) -> Ordering {
+        a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+    }
+
+    fn add_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+        let mut digits = Vec::with_capacity(a.len().max(b.len()) + 1);
+        let mut carry = 0u64;
+        for i in 0..a.len().max(b.len()) {
+            let t = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+            digits.push(t as u32);
+            carry = t >> 32;
+        }
+        if carry != 0 {
+            digits.push(carry as u32);
+        }
+        digits
+    }
+
+    // Requires a >= b
+    fn sub_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+        let mut digits = Vec::with_capacity(a.len());
+        let mut borrow = 0i64;
+        for (i, &d) in a.iter().enumerate() {
+            let mut t = d as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = if t < 0 { t += 1 << 32; 1 } else { 0 };
+            digits.push(t as u32);
+        }
+        digits
+    }
+
+    fn div_digit(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+        let mut digits = vec![0u32; a.len()];
+        let mut remainder = 0u64;
+        for i in (0..a.len()).rev() {
+            let t = remainder << 32 | a[i] as u64;
+            digits[i] = (t / divisor as u64) as u32;
+            remainder = t % divisor as u64;
+        }
+        (digits, remainder as u32)
+    }
+
+    fn is_foreign(x: &RibField, holder: &mut RibHeap) -> bool {
+        match x.as_rib() {
+            Some(inner) => holder.get(&inner).last == RibField::number(FOREIGN),
+            None => false,
+        }
+    }
+
+    fn is_bignum(x: &RibField, holder: &mut RibHeap) -> bool {
+        match x.as_rib() {
+            Some(inner) => holder.get(&inner).last == RibField::number(BIGNUM),
+            None => false,
+        }
+    }
+
+    fn get_bigint(x: &RibField, holder: &mut RibHeap) -> Result<BigInt, VmError> {
+        if !is_bignum(x, holder) {
+            return Ok(BigInt::from_i64(x.get_number()?))
+        }
+        let rib = x.get_rib(holder)?;
+        let mut digits = Vec::new();
+        let mut list = rib.first;
+        while list != RibField::rib(NIL_REF) {
+            let pair = list.get_rib(holder)?;
+            digits.push(pair.first.get_number()? as u32);
+            list = pair.middle;
+        }
+        Ok(BigInt { negative: rib.middle == RibField::number(-1), digits })
+    }
+
+    fn bigint_to_rib(n: BigInt, holder: &mut RibHeap) -> RibField {
+        if let Some(fixnum) = n.to_i64().and_then(RibField::fixnum) {
+            return fixnum
+        }
+        let mut digits = NIL_REF;
+        for &d in n.digits.iter().rev() {
+            push_stack(RibField::number(d as i64), &mut digits, holder);
+        }
+        let sign = if n.negative { -1 } else { 1 };
+        RibField::rib(holder.push_rib(make_data_rib(RibField::rib(digits),
+                                                    RibField::number(sign),
+                                                    BIGNUM)))
+    }
+
+    // Flonums
+//
+// Floating-point numbers are boxed in a rib holding the bits of the f64.
+// Mixing a flonum with an exact integer in arithmetic gives a flonum. Like
+// bignums, flonums are not ribs for rib?.
+
+    fn is_flonum(x: &RibField, holder: &mut RibHeap) -> bool {
+        match x.as_rib() {
+            Some(inner) => holder.get(&inner).last == RibField::number(FLONUM),
+            None => false,
+        }
+    }
+
+    fn get_flonum(x: &RibField, holder: &mut RibHeap) -> Result<f64, VmError> {
+        if is_flonum(x, holder) {
+            let rib = x.get_rib(holder)?;
+            let high = rib.first.get_number()? as u64;
+            let low = rib.middle.get_number()? as u64;
+            Ok(f64::from_bits(high << 32 | low))
+        } else if is_bignum(x, holder) {
+            Ok(get_bigint(x, holder)?.to_f64())
+        } else {
+            Ok(x.get_number()? as f64)
+        }
+    }
+
+    fn flonum_to_rib(f: f64, holder: &mut RibHeap) -> RibField {
+        let bits = f.to_bits();
+        RibField::rib(holder.push_rib(make_data_rib(RibField::number((bits >> 32) as i64),
+                                                    RibField::number(bits as u32 as i64),
+                                                    FLONUM)))
+    }
+
+    fn flonum_to_string(f: f64) -> String {
+        if f.is_nan() {
+            String::from("+nan.0")
+        } else if f.is_infinite() {
+            String::from(if f > 0.0 { "+inf.0" } else { "-inf.0" })
+        } else {
+            format!("{:?}", f)
+        }
+    }
+
+    fn string_to_flonum(s: &str) -> Option<f64> {
+        match s {
+            "+inf.0" => Some(f64::INFINITY),
+            "-inf.0" => Some(f64::NEG_INFINITY),
+            "+nan.0" | "-nan.0" => Some(f64::NAN),
+            // Only the decimal notation, things like "inf" are symbols
+            _ if s.contains(['.', 'e', 'E']) &&
+                s.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c)) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn is_number(x: &RibField, holder: &mut RibHeap) -> bool {
+        !is_rib(x) || is_bignum(x, holder) || is_flonum(x, holder)
+    }
+
+    // Fixnum operands take the fast path unless the result overflows, a
+    // flonum operand makes the operation inexact and anything else goes
+    // through bignum arithmetic.
+    fn rvm_arith(x: RibField, y: RibField, holder: &mut RibHeap,
+                 fixnum_op: fn(i64, i64) -> Option<i64>,
+                 bignum_op: fn(&BigInt, &BigInt) -> BigInt,
+                 flonum_op: fn(f64, f64) -> f64,
+                 type_error: &str) -> Result<RibField, VmError> {
+        if let (Some(a), Some(b)) = (x.as_number(), y.as_number()) {
+            if let Some(n) = fixnum_op(a, b).and_then(RibField::fixnum) {
+                return Ok(n)
+            }
+        }
+        if !is_number(&x, holder) || !is_number(&y, holder) {
+            return Err(VmError::TypeError(String::from(type_error)))
+        }
+        if is_flonum(&x, holder) || is_flonum(&y, holder) {
+            let a = get_flonum(&x, holder)?;
+            let b = get_flonum(&y, holder)?;
+            return Ok(flonum_to_rib(flonum_op(a, b), holder))
+        }
+        let a = get_bigint(&x, holder)?;
+        let b = get_bigint(&y, holder)?;
+        Ok(bigint_to_rib(bignum_op(&a, &b), holder))
+    }
+
+    fn rvm_less(x: RibField, y: RibField, holder: &mut RibHeap) -> Result<bool, VmError> {
+        if let (Some(a), Some(b)) = (x.as_number(), y.as_number()) {
+            return Ok(a < b)
+        }
+        if !is_number(&x, holder) || !is_number(&y, holder) {
+            return Err(VmError::TypeError(String::from("Comparison operands should both be numbers")))
+        }
+        if is_flonum(&x, holder) || is_flonum(&y, holder) {
+            return Ok(get_flonum(&x, holder)? < get_flonum(&y, holder)?)
+        }
+        Ok(get_bigint(&x, holder)? < get_bigint(&y, holder)?)
+    }
+
+    fn is_zero(x: &RibField, holder: &mut RibHeap) -> Result<bool, VmError> {
+        Ok(*x == RibField::number(0) || (is_flonum(x, holder) && get_flonum(x, holder)? == 0.0))
+    }
+
+    // floor, ceiling, truncate and round leave exact integers alone
+    fn rvm_round(x: RibField, holder: &mut RibHeap, f: fn(f64) -> f64) -> Result<RibField, VmError> {
+        if !is_flonum(&x, holder) {
+            if !is_number(&x, holder) {
+                return Err(VmError::TypeError(String::from("Rounding operand should be a number")))
+            }
+            return Ok(x)
+        }
+        let v = get_flonum(&x, holder)?;
+        Ok(flonum_to_rib(f(v), holder))
+    }
+
+    fn rvm_flonum_fn(x: RibField, holder: &mut RibHeap, f: fn(f64) -> f64) -> Result<RibField, VmError> {
+        let v = get_flonum(&x, holder)?;
+        Ok(flonum_to_rib(f(v), holder))
     }
 
-    fn make_op_rib(first: i32, middle: RibField, last: RibField) -> Rib {
-        make_rib(RibField::Number(first), middle, last)
+    // Rounds to even on ties, like Scheme's round
+    fn round_to_even(f: f64) -> f64 {
+        if (f - f.trunc()).abs() == 0.5 { 2.0 * (f / 2.0).round() } else { f.round() }
     }
 
 
@@ -507,53 +1718,53 @@ pub mod rvm {
 
 
     // @@(feature debug
-    fn show(o: &RibField, holder: &mut RibHeap) -> String{
-        if !is_rib(o) {o.get_number().to_string()}
+    fn show(o: &RibField, holder: &mut RibHeap) -> Result<String, VmError> {
+        if !is_rib(o) {Ok(o.get_number()?.to_string())}
         else {
-            let mut rib_o = o.get_rib(holder);
+            let mut rib_o = o.get_rib(holder)?;
             let kind = rib_o.last;
             let mut result = String::new();
-            match kind {
-                RibField::Number(ref n) => match *n {
+            match kind.as_number() {
+                Some(n) => match n {
                     VECTOR => {result = String::from("#");
-                        result.push_str(show(&rib_o.first,holder).as_str());
+                        result.push_str(show(&rib_o.first,holder)?.as_str());
                     },
                     PAIR => { // Could also be tail call
                         let mut n =1;
                         result.push('(');
-                        result.push_str(show(&rib_o.first, holder).as_str());
+                        result.push_str(show(&rib_o.first, holder)?.as_str());
                         let mut o_middle = rib_o.middle;
                         while is_rib(&o_middle) &&
-                            (!is_rib(&o_middle.get_rib(holder).last) &&
-                                o_middle.get_rib(holder).last.get_number() == 0)
+                            (!is_rib(&o_middle.get_rib(holder)?.last) &&
+                                o_middle.get_rib(holder)?.last.get_number()? == 0)
                         {
-                            rib_o = o_middle.get_rib(holder);
+                            rib_o = o_middle.get_rib(holder)?;
                             if n > 4 {
                                 result.push_str(" ...");
-                                o_middle = RibField::Rib(NIL_REF);
+                                o_middle = RibField::rib(NIL_REF);
                                 break;
                             }
                             result.push(' ');
-                            result.push_str(show(&rib_o.first, holder).as_str());
+                            result.push_str(show(&rib_o.first, holder)?.as_str());
                             o_middle = rib_o.middle;
                             n += 1;
                         }
-                        if o_middle != RibField::Rib(NIL_REF)
+                        if o_middle != RibField::rib(NIL_REF)
                         {
                             result.push_str(" . ");
-                            result.push_str(show(&o_middle, holder).as_str());
+                            result.push_str(show(&o_middle, holder)?.as_str());
                         }
                         result.push(')');
                     },
                     PROCEDURE => {
                         if is_rib(&rib_o.first) {
-                            let rib_o_first = rib_o.first.get_rib(holder);
+                            let rib_o_first = rib_o.first.get_rib(holder)?;
                             result.push_str("#<procedure nparams=");
-                            result.push_str(rib_o_first.first.get_number().to_string().as_str());
+                            result.push_str(rib_o_first.first.get_number()?.to_string().as_str());
                             result.push('>');
                         } else {
                             result.push_str("#<primitive ");
-                            result.push_str(rib_o.first.get_number().to_string().as_str());
+                            result.push_str(rib_o.first.get_number()?.to_string().as_str());
                             result.push('>');
                         }
                     },
@@ -561,18 +1772,18 @@ pub mod rvm {
                         let mut field_o = rib_o.middle;
                         let mut cond = is_rib(&field_o);
                         if cond {
-                            rib_o =field_o.get_rib(holder);
-                            if (!is_rib(&rib_o.last) && rib_o.last.get_number() ==2) &&
-                                (!is_rib(&rib_o.middle) && rib_o.middle.get_number() > 0)
+                            rib_o =field_o.get_rib(holder)?;
+                            if (!is_rib(&rib_o.last) && rib_o.last.get_number()? ==2) &&
+                                (!is_rib(&rib_o.middle) && rib_o.middle.get_number()? > 0)
                             {
                                 field_o = rib_o.first;
                                 while is_rib(&field_o) &&
-                                    !is_rib(&field_o.get_rib(holder).last) &&
-                                    field_o.get_rib(holder).last.get_number() == 0
+                                    !is_rib(&field_o.get_rib(holder)?.last) &&
+                                    field_o.get_rib(holder)?.last.get_number()? == 0
                                 {
-                                    rib_o =field_o.get_rib(holder);
-                                    let n =rib_o.first.get_number() as u32;
-                                    let c = std::char::from_u32(n).unwrap();
+                                    rib_o =field_o.get_rib(holder)?;
+                                    let n =rib_o.first.get_number()?;
+                                    let c = to_char(n)?;
                                     result.push(c);
                                     field_o = rib_o.middle;
                                 }
@@ -580,9 +1791,9 @@ pub mod rvm {
                             else
                             { cond = false; }
                         }
-                        if cond == false {
+                        if !cond {
                             result.push_str("#<symbol ");
-                            let sh =show(&field_o, holder);
+                            let sh =show(&field_o, holder)?;
                             result.push_str(sh.as_str());
 
                             result.push('>');
@@ -592,15 +1803,15 @@ pub mod rvm {
                         result.push('"');
                         let mut field_o = rib_o.first;
 
-                        while is_rib(&field_o) && !is_rib(&field_o.get_rib(holder).last)
-                            && field_o.get_rib(holder).last.get_number() == 0
+                        while is_rib(&field_o) && !is_rib(&field_o.get_rib(holder)?.last)
+                            && field_o.get_rib(holder)?.last.get_number()? == 0
                         {
-                            rib_o = field_o.get_rib(holder);
-                            let n = rib_o.first.get_number() as u32;
-                            let mut c =std::char::from_u32(n).unwrap();
+                            rib_o = field_o.get_rib(holder)?;
+                            let n = rib_o.first.get_number()?;
+                            let mut c =to_char(n)?;
                             if c == '\n' {
                                 c = 'n';
-                                result.push_str("\\");
+                                result.push('\\');
                             } else if c == '\r' {
                                 c = 'r';
                                 result.push('\\');
@@ -616,20 +1827,32 @@ pub mod rvm {
                         result.push('"');
 
                     },
+                    BIGNUM => result.push_str(get_bigint(o, holder)?.to_string().as_str()),
+                    FLONUM => result.push_str(flonum_to_string(get_flonum(o, holder)?).as_str()),
+                    WEAK_BOX => result.push_str("#<weak-box>"),
+                    EPHEMERON => result.push_str("#<ephemeron>"),
+                    FOREIGN => {
+                        result.push_str("#<foreign ");
+                        match holder.foreign.get(rib_o.middle.get_number()? as usize) {
+                            Some(Some(foreign)) => result.push_str(foreign.type_name),
+                            _ => result.push_str("dropped"),
+                        }
+                        result.push('>');
+                    },
                     SPECIAL => {
-                        match o {
-                            RibField::Rib(FALSE_REF) => result.push_str("#f"),
-                            RibField::Rib(TRUE_REF) => result.push_str("#t"),
-                            RibField::Rib(NIL_REF) => result.push_str("()"),
+                        match o.as_rib() {
+                            Some(FALSE_REF) => result.push_str("#f"),
+                            Some(TRUE_REF) => result.push_str("#t"),
+                            Some(NIL_REF) => result.push_str("()"),
                             _ => {
                                 result.push('[');
-                                let sh =show(&rib_o.first, holder);
+                                let sh =show(&rib_o.first, holder)?;
                                 result.push_str(sh.as_str());
                                 result.push(',');
-                                let sh = show(&rib_o.middle, holder);
+                                let sh = show(&rib_o.middle, holder)?;
                                 result.push_str(sh.as_str());
                                 result.push(',');
-                                let sh = show(&rib_o.last, holder);
+                                let sh = show(&rib_o.last, holder)?;
                                 result.push_str(sh.as_str());
                                 result.push(']');
                             }
@@ -637,36 +1860,36 @@ pub mod rvm {
                     },
                     _ => {
                         result.push('[');
-                        let sh =show(&rib_o.first, holder);
+                        let sh =show(&rib_o.first, holder)?;
                         result.push_str(sh.as_str());
                         result.push(',');
-                        let sh =show(&rib_o.middle, holder);
+                        let sh =show(&rib_o.middle, holder)?;
                         result.push_str(sh.as_str());
                         result.push(',');
-                        let sh =show(&rib_o.last, holder);
+                        let sh =show(&rib_o.last, holder)?;
                         result.push_str(sh.as_str());
                         result.push(']');
                     }
                 },
-                RibField::Rib(_) => {
+                None => {
                     result.push('[');
-                    let sh =show(&rib_o.first, holder);
+                    let sh =show(&rib_o.first, holder)?;
                     result.push_str(sh.as_str());
                     result.push(',');
-                    let sh = show(&rib_o.middle, holder);
+                    let sh = show(&rib_o.middle, holder)?;
                     result.push_str(sh.as_str());
                     result.push(',');
-                    let sh =show(&rib_o.last, holder);
+                    let sh =show(&rib_o.last, holder)?;
                     result.push_str(sh.as_str());
                     result.push(']');
                 }
             };
-            result
+            Ok(result)
         }
     }
 
     fn start_step(step_count: &mut u32, tracing: &mut bool, next_stamp: &mut u32,
-                  start_tracing: &u32, stack: &usize, holder: &mut RibHeap) {
+                  start_tracing: &u32, stack: &usize, holder: &mut RibHeap) -> Result<(), VmError> {
         *step_count += 1;
         if *step_count >= *start_tracing {
             *tracing = true;
@@ -675,152 +1898,562 @@ pub mod rvm {
             if *step_count >= *next_stamp
             {
                 *next_stamp = f32::floor((*next_stamp as f32) *1.01 + 1.0) as u32;
-                eprintln!("@{}",step_count.to_string());
+                eprintln!("@{}",step_count);
             }
-            return
+            return Ok(())
         }
-        let mut s = RibField::Rib(*stack);
-        let mut rib_s = s.get_rib(holder);
+        let mut s = RibField::rib(*stack);
+        let mut rib_s = s.get_rib(holder)?;
         let mut result = String::new();
         result.push('@');
         result.push_str(step_count.to_string().as_str());
         result.push_str(" STACK = (");
-        while !is_rib(&rib_s.last) && rib_s.last.get_number() == 0
+        while !is_rib(&rib_s.last) && rib_s.last.get_number()? == 0
         {
             result.push(' ');
-            let sh = show(&rib_s.first,holder);
+            let sh = show(&rib_s.first,holder)?;
             result.push_str(sh.as_str());
             s = rib_s.middle;
             if !is_rib(&s) {break;}
-            rib_s = s.get_rib(holder);
+            rib_s = s.get_rib(holder)?;
         }
         result.push(')');
         eprintln!("{}",result);
-
+        Ok(())
     }
     // )@@
 
 
     fn is_rib(obj: &RibField) -> bool {
-        match obj {
-            RibField::Rib(_) => true,
-            _ => false,
-        }
+        obj.is_rib()
     }
 
 
 
 
+    // Conversions between host and Scheme values (RFS-0), see also str2scm
+    // and scm2str
+
     // @@(feature bool2scm
     fn to_bool<E>(expr: E) -> RibField where E: FnOnce() -> bool{
-        if expr() { RibField::Rib(TRUE_REF)} else { RibField::Rib(FALSE_REF) }
+        if expr() { RibField::rib(TRUE_REF)} else { RibField::rib(FALSE_REF) }
     }
     // )@@
 
+    // @@(feature scm2bool
+    fn scm2bool(x: &RibField) -> bool {
+        *x != RibField::rib(FALSE_REF)
+    }
+    // )@@
 
+    // @@(feature int2scm
+    fn int2scm(n: i64, holder: &mut RibHeap) -> RibField {
+        match RibField::fixnum(n) {
+            Some(x) => x,
+            None => bigint_to_rib(BigInt::from_i64(n), holder),
+        }
+    }
+    // )@@
 
+    // @@(feature scm2int
+    fn scm2int(x: &RibField, holder: &mut RibHeap) -> Result<i64, VmError> {
+        match x.as_number() {
+            Some(n) => Ok(n),
+            None if is_bignum(x, holder) => {
+                let n = get_bigint(x, holder)?;
+                n.to_i64().ok_or_else(|| VmError::TypeError(format!("Integer {} out of range", n)))
+            },
+            _ => Err(VmError::TypeError(String::from("Expected an integer"))),
+        }
+    }
+    // )@@
 
+    // @@(feature char2scm
+    fn char2scm(c: char) -> RibField {
+        RibField::number(c as i64)
+    }
+    // )@@
 
-    //functions involving the stack
+    // @@(feature scm2char
+    fn scm2char(x: &RibField) -> Result<char, VmError> {
+        match x.as_number() {
+            Some(n) => to_char(n),
+            None => Err(VmError::TypeError(String::from("Expected a character"))),
+        }
+    }
+    // )@@
 
-    fn push_stack(x: RibField, stack: &mut usize, holder:&mut RibHeap){
-        *stack = holder.push_rib(make_data_rib(x,
-                                               RibField::Rib(*stack),
-                                               PAIR));
+    // @@(feature list2scm
+    fn list2scm(elems: &[RibField], holder: &mut RibHeap) -> RibField {
+        let mut list = NIL_REF;
+        for elem in elems.iter().rev() {
+            push_stack(*elem, &mut list, holder);
+        }
+        RibField::rib(list)
     }
+    // )@@
 
-    fn pop_stack(stack: &mut usize, holder: &mut RibHeap) ->RibField{
-        let r = holder.get(&stack).first;
-        *stack = holder.get(&stack).middle.get_rib_ref();
-        r
+    // @@(feature scm2list
+    fn scm2list(x: &RibField, holder: &mut RibHeap) -> Result<Vec<RibField>, VmError> {
+        let mut elems = Vec::new();
+        let mut list = *x;
+        while list != RibField::rib(NIL_REF) {
+            if !is_rib(&list) || list.get_rib(holder)?.last != RibField::number(PAIR) {
+                return Err(VmError::TypeError(String::from("Expected a list")))
+            }
+            let pair = list.get_rib(holder)?;
+            elems.push(pair.first);
+            list = pair.middle;
+        }
+        Ok(elems)
     }
+    // )@@
 
-    fn rvm_getchar(stack: &mut usize, holder: &mut RibHeap) {
-        let mut buf: [u8; 1] = [0; 1];
-        stdin()
-            .read(&mut buf)
-            .expect("Failed to read character in standard input");
-        let n = from_utf8(&buf).unwrap();
-        let c =n.chars().next().unwrap();
-        if c as i32 == 0
-        {
-            push_stack(RibField::Number(-1), stack, holder);
-        } else {
-        push_stack(RibField::Number(c as i32), stack, holder);
+    // @@(feature vector2scm (use list2scm)
+    fn vector2scm(elems: &[RibField], holder: &mut RibHeap) -> RibField {
+        let list = list2scm(elems, holder);
+        RibField::rib(holder.push_rib(make_data_rib(list,
+                                                    RibField::number(elems.len() as i64),
+                                                    VECTOR)))
+    }
+    // )@@
+
+    // @@(feature scm2vector (use scm2list)
+    fn scm2vector(x: &RibField, holder: &mut RibHeap) -> Result<Vec<RibField>, VmError> {
+        if !is_rib(x) || x.get_rib(holder)?.last != RibField::number(VECTOR) {
+            return Err(VmError::TypeError(String::from("Expected a vector")))
         }
+        let list = x.get_rib(holder)?.first;
+        scm2list(&list, holder)
     }
+    // )@@
 
+    // @@(feature host2scm (use bool2scm int2scm char2scm list2scm vector2scm)
+    /// A Rust value with a Scheme counterpart, which `to_scheme` builds.
+    /// Integers become fixnums (or bignums past `i64`), `f64` flonums,
+    /// strings Scheme strings, `Vec` lists and `Vector` vectors.
+    pub trait ToScheme {
+        /// Builds the Scheme value of `self` on `heap`.
+        fn to_scheme(&self, heap: &mut RibHeap) -> RibField;
+    }
 
-    fn rvm_prim1<F>(
-        // @@(feature arity-check
+    /// A vector, as opposed to a list, when converted with `ToScheme` and
+    /// `FromScheme`.
+    #[derive(Debug,Clone,PartialEq)]
+    pub struct Vector<T>(pub Vec<T>);
+
+    impl ToScheme for RibField {
+        fn to_scheme(&self, _heap: &mut RibHeap) -> RibField {
+            *self
+        }
+    }
+
+    impl ToScheme for bool {
+        fn to_scheme(&self, _heap: &mut RibHeap) -> RibField {
+            to_bool(|| *self)
+        }
+    }
+
+    macro_rules! small_int_to_scheme {
+        ($($t:ty)*) => {$(
+            impl ToScheme for $t {
+                fn to_scheme(&self, heap: &mut RibHeap) -> RibField {
+                    int2scm(*self as i64, heap)
+                }
+            }
+        )*}
+    }
+
+    small_int_to_scheme! { i8 i16 i32 u8 u16 u32 }
+
+    impl ToScheme for i64 {
+        fn to_scheme(&self, heap: &mut RibHeap) -> RibField {
+            int2scm(*self, heap)
+        }
+    }
+
+    impl ToScheme for isize {
+        fn to_scheme(&self, heap: &mut RibHeap) -> RibField {
+            (*self as i64).to_scheme(heap)
+        }
+    }
+
+    impl ToScheme for u64 {
+        fn to_scheme(&self, heap: &mut RibHeap) -> RibField {
+            if *self <= MAX_FIXNUM as u64 {
+                int2scm(*self as i64, heap)
+            } else {
+                let n = BigInt { negative: false, digits: vec![*self as u32, (*self >> 32) as u32] };
+                bigint_to_rib(n, heap)
+            }
+        }
+    }
+
+    impl ToScheme for usize {
+        fn to_scheme(&self, heap: &mut RibHeap) -> RibField {
+            (*self as u64).to_scheme(heap)
+        }
+    }
+
+    impl ToScheme for f64 {
+        fn to_scheme(&self, heap: &mut RibHeap) -> RibField {
+            flonum_to_rib(*self, heap)
+        }
+    }
+
+    impl ToScheme for char {
+        fn to_scheme(&self, _heap: &mut RibHeap) -> RibField {
+            char2scm(*self)
+        }
+    }
+
+    impl ToScheme for str {
+        fn to_scheme(&self, heap: &mut RibHeap) -> RibField {
+            str2scm(self, heap)
+        }
+    }
+
+    impl ToScheme for String {
+        fn to_scheme(&self, heap: &mut RibHeap) -> RibField {
+            str2scm(self, heap)
+        }
+    }
+
+    impl<T: ToScheme> ToScheme for [T] {
+        fn to_scheme(&self, heap: &mut RibHeap) -> RibField {
+            let elems: Vec<RibField> = self.iter().map(|elem| elem.to_scheme(heap)).collect();
+            list2scm(&elems, heap)
+        }
+    }
+
+    impl<T: ToScheme> ToScheme for Vec<T> {
+        fn to_scheme(&self, heap: &mut RibHeap) -> RibField {
+            self[..].to_scheme(heap)
+        }
+    }
+
+    impl<T: ToScheme> ToScheme for Vector<T> {
+        fn to_scheme(&self, heap: &mut RibHeap) -> RibField {
+            let elems: Vec<RibField> = self.0.iter().map(|elem| elem.to_scheme(heap)).collect();
+            vector2scm(&elems, heap)
+        }
+    }
+    // )@@
+
+    // @@(feature scm2host (use scm2bool scm2int scm2char scm2list scm2vector)
+    /// A Rust value that `from_scheme` can extract from a Scheme value,
+    /// the reverse of `ToScheme`. Values of the wrong type or out of the
+    /// range of the Rust type give a `VmError::TypeError`.
+    pub trait FromScheme: Sized {
+        /// Converts `value`, which lives on `heap`.
+        fn from_scheme(value: &RibField, heap: &mut RibHeap) -> Result<Self, VmError>;
+    }
+
+    impl FromScheme for RibField {
+        fn from_scheme(value: &RibField, _heap: &mut RibHeap) -> Result<Self, VmError> {
+            Ok(*value)
+        }
+    }
+
+    impl FromScheme for bool {
+        fn from_scheme(value: &RibField, _heap: &mut RibHeap) -> Result<Self, VmError> {
+            Ok(scm2bool(value))
+        }
+    }
+
+    macro_rules! small_int_from_scheme {
+        ($($t:ty)*) => {$(
+            impl FromScheme for $t {
+                fn from_scheme(value: &RibField, heap: &mut RibHeap) -> Result<Self, VmError> {
+                    let n = scm2int(value, heap)?;
+                    let m = n as $t;
+                    if m as i64 != n {
+                        return Err(VmError::TypeError(format!("Integer {} out of range", n)))
+                    }
+                    Ok(m)
+                }
+            }
+        )*}
+    }
+
+    small_int_from_scheme! { i8 i16 i32 i64 isize u8 u16 u32 }
+
+    impl FromScheme for u64 {
+        fn from_scheme(value: &RibField, heap: &mut RibHeap) -> Result<Self, VmError> {
+            if is_bignum(value, heap) {
+                let n = get_bigint(value, heap)?;
+                if !n.negative && n.digits.len() <= 2 {
+                    return Ok(n.digits.iter().rev().fold(0, |acc, d| acc << 32 | *d as u64))
+                }
+            }
+            let n = scm2int(value, heap)?;
+            if n < 0 {
+                return Err(VmError::TypeError(format!("Integer {} out of range", n)))
+            }
+            Ok(n as u64)
+        }
+    }
+
+    impl FromScheme for usize {
+        fn from_scheme(value: &RibField, heap: &mut RibHeap) -> Result<Self, VmError> {
+            let n = u64::from_scheme(value, heap)?;
+            if n > usize::MAX as u64 {
+                return Err(VmError::TypeError(format!("Integer {} out of range", n)))
+            }
+            Ok(n as usize)
+        }
+    }
+
+    impl FromScheme for f64 {
+        fn from_scheme(value: &RibField, heap: &mut RibHeap) -> Result<Self, VmError> {
+            get_flonum(value, heap)
+        }
+    }
+
+    impl FromScheme for char {
+        fn from_scheme(value: &RibField, _heap: &mut RibHeap) -> Result<Self, VmError> {
+            scm2char(value)
+        }
+    }
+
+    impl FromScheme for String {
+        fn from_scheme(value: &RibField, heap: &mut RibHeap) -> Result<Self, VmError> {
+            scm2str(value, heap)
+        }
+    }
+
+    impl<T: FromScheme> FromScheme for Vec<T> {
+        fn from_scheme(value: &RibField, heap: &mut RibHeap) -> Result<Self, VmError> {
+            let elems = scm2list(value, heap)?;
+            elems.iter().map(|elem| T::from_scheme(elem, heap)).collect()
+        }
+    }
+
+    impl<T: FromScheme> FromScheme for Vector<T> {
+        fn from_scheme(value: &RibField, heap: &mut RibHeap) -> Result<Self, VmError> {
+            let elems = scm2vector(value, heap)?;
+            elems.iter().map(|elem| T::from_scheme(elem, heap)).collect::<Result<_, _>>().map(Vector)
+        }
+    }
+    // )@@
+
+
+
+
+
+    //functions involving the stack
+
+    fn push_stack(x: RibField, stack: &mut usize, holder:&mut RibHeap){
+        *stack = holder.push_rib(make_data_rib(x,
+                                               RibField::rib(*stack),
+                                               PAIR));
+    }
+
+    #[inline]
+    fn pop_stack(stack: &mut usize, holder: &mut RibHeap) -> Result<RibField, VmError>{
+        let r = holder.get(stack).first;
+        *stack = holder.get(stack).middle.get_rib_ref()?;
+        Ok(r)
+    }
+
+    // The stack of the program. The values pushed since the last call are
+    // kept in a buffer rather than in pairs, until a closure or the
+    // collector needs the stack as ribs. The frames of the procedures are
+    // always ribs, so that call/cc sees the same stack either way.
+    struct Stack {
+        top: Vec<RibField>, // the last value is the top of the stack
+        ribs: usize,        // the rest of the stack
+        args: Vec<RibField>, // reused to move the arguments of a call
+    }
+
+    impl Stack {
+        fn new(ribs: usize) -> Stack {
+            Stack { top: Vec::new(), ribs, args: Vec::new() }
+        }
+
+        #[inline]
+        fn push(&mut self, x: RibField) {
+            self.top.push(x);
+        }
+
+        #[inline]
+        fn pop(&mut self, holder: &mut RibHeap) -> Result<RibField, VmError> {
+            match self.top.pop() {
+                Some(x) => Ok(x),
+                None => pop_stack(&mut self.ribs, holder),
+            }
+        }
+
+        // Moves the buffer to pairs and returns the whole stack as a rib
+        fn spill(&mut self, holder: &mut RibHeap) -> usize {
+            for x in self.top.drain(..) {
+                push_stack(x, &mut self.ribs, holder);
+            }
+            self.ribs
+        }
+
+        // Replaces the stack with ribs
+        fn reset(&mut self, ribs: usize) {
+            self.top.clear();
+            self.ribs = ribs;
+        }
+
+        // The frame of the running procedure, the first one below the top
+        fn cont(&self, holder: &mut RibHeap) -> Result<usize, VmError> {
+            get_cont(&self.ribs, holder)
+        }
+
+        // The value of the operand o of get and call: a slot of the stack
+        // or a global variable
+        #[inline]
+        fn get(&self, o: &RibField, holder: &mut RibHeap) -> Result<RibField, VmError> {
+            let len = self.top.len();
+            match o.as_number() {
+                Some(n) if (n as usize) < len => Ok(self.top[len - 1 - n as usize]),
+                Some(n) => {
+                    let slot = list_tail(&self.ribs, (n as usize - len) as u32, holder)?;
+                    Ok(holder.get(&slot).first)
+                },
+                None => Ok(holder.get(&o.get_rib_ref()?).first),
+            }
+        }
+
+        // Pops the top of the stack into the operand o of set, which
+        // designates its slot as before the pop
+        #[inline]
+        fn set(&mut self, o: &RibField, holder: &mut RibHeap) -> Result<(), VmError> {
+            let len = self.top.len();
+            let index = match o.as_number() {
+                Some(n) if (n as usize) < len => {
+                    let x = self.pop(holder)?;
+                    if let Some(slot) = self.top.get_mut(len - 1 - n as usize) {
+                        *slot = x;
+                    }
+                    return Ok(())
+                },
+                Some(n) => list_tail(&self.ribs, (n as usize - len) as u32, holder)?,
+                None => o.get_rib_ref()?,
+            };
+            let mut rib = holder.get(&index);
+            rib.first = self.pop(holder)?;
+            holder.set(&index, rib);
+            Ok(())
+        }
+    }
+
+    // Decodes one UTF-8 character, None at the end of the input. An invalid
+    // sequence is read as a single U+FFFD; the byte that makes it invalid is
+    // not consumed unless it is the first one, so it starts the next character.
+    fn read_utf8_char<R: BufRead>(input: &mut R) -> std::io::Result<Option<char>> {
+        let lead = match input.fill_buf()?.first() {
+            Some(&byte) => byte,
+            None => return Ok(None),
+        };
+        input.consume(1);
+        let width = match lead {
+            0x00..=0x7F => return Ok(Some(lead as char)),
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => return Ok(Some(char::REPLACEMENT_CHARACTER)),
+        };
+        let mut bytes = vec![lead];
+        while bytes.len() < width {
+            match input.fill_buf()?.first() {
+                Some(&byte) if byte & 0xC0 == 0x80 => {
+                    bytes.push(byte);
+                    input.consume(1);
+                },
+                _ => return Ok(Some(char::REPLACEMENT_CHARACTER)),
+            }
+        }
+        // Overlong encodings, surrogates and code points above U+10FFFF
+        // get past the checks above
+        Ok(Some(from_utf8(&bytes).ok()
+                .and_then(|s| s.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER)))
+    }
+
+    fn rvm_getchar<R: BufRead, W: Write>(stack: &mut Stack,
+                                         input: &mut R, output: &mut W) -> Result<(), VmError> {
+        flush_output(output)?;
+        let c = read_utf8_char(input)
+            .map_err(|err| VmError::Io(format!("Failed to read input: {}", err)))?;
+        match c {
+            Some(c) => stack.push(RibField::number(c as i64)),
+            None => stack.push(RibField::number(-1)),
+        }
+        Ok(())
+    }
+
+
+    fn rvm_prim1<F>(
+        // @@(feature arity-check
         expected_nargs: u32,
         // )@@
-        mut f: F,stack: &mut usize, holder: &mut RibHeap)
-        where F: FnMut(RibField,&mut RibHeap) -> RibField{
+        mut f: F,stack: &mut Stack, holder: &mut RibHeap) -> Result<(), VmError>
+        where F: FnMut(RibField,&mut RibHeap) -> Result<RibField, VmError>{
         // @@(feature arity-check
         if expected_nargs != 1
         {
-            incoherent_nargs_stop(expected_nargs,1,false)
+            return Err(incoherent_nargs_stop(expected_nargs,1,false))
         }
         // )@@
-        let x =pop_stack(stack, holder);
-        let r = f(x, holder);
-        push_stack(
-            r,
-            stack, holder
-        );
+        let x = stack.pop(holder)?;
+        let r = f(x, holder)?;
+        stack.push(r);
+        Ok(())
     }
 
     fn rvm_prim2<G>(
         // @@(feature arity-check
         expected_nargs: u32,
                      // )@@
-                     mut f: G,stack: &mut usize, holder: &mut RibHeap)
-        where G: FnMut(RibField,RibField, &mut RibHeap) -> RibField{
+                     mut f: G,stack: &mut Stack, holder: &mut RibHeap) -> Result<(), VmError>
+        where G: FnMut(RibField,RibField, &mut RibHeap) -> Result<RibField, VmError>{
         // @@(feature arity-check
         if expected_nargs != 2
         {
-            incoherent_nargs_stop(expected_nargs,2,false)
+            return Err(incoherent_nargs_stop(expected_nargs,2,false))
         }
         // )@@
-        let x = pop_stack(stack, holder);
-        let y = pop_stack(stack, holder);
-        let r =f(x, y, holder);
-        push_stack(r,
-                   stack, holder
-        );
+        let x = stack.pop(holder)?;
+        let y = stack.pop(holder)?;
+        let r =f(x, y, holder)?;
+        stack.push(r);
+        Ok(())
     }
 
     fn rvm_prim3<H>(
         // @@(feature arity-check
                     expected_nargs: u32,
                     // )@@
-                    mut f: H,stack: &mut usize, holder: &mut RibHeap)
-        where H: FnMut(RibField, RibField, RibField, &mut RibHeap) -> RibField{
+                    mut f: H,stack: &mut Stack, holder: &mut RibHeap) -> Result<(), VmError>
+        where H: FnMut(RibField, RibField, RibField, &mut RibHeap) -> Result<RibField, VmError>{
         // @@(feature arity-check
         if expected_nargs != 3
         {
-            incoherent_nargs_stop(expected_nargs,3,false)
+            return Err(incoherent_nargs_stop(expected_nargs,3,false))
         }
         // )@@
-        let x = pop_stack(stack, holder);
-        let y = pop_stack(stack, holder);
-        let z = pop_stack(stack, holder);
-        let r = f(x,y,z, holder);
-        push_stack(r,
-                   stack, holder
-        );
+        let x = stack.pop(holder)?;
+        let y = stack.pop(holder)?;
+        let z = stack.pop(holder)?;
+        let r = f(x,y,z, holder)?;
+        stack.push(r);
+        Ok(())
     }
 
-    fn rvm_arg2(stack: &mut usize, holder: &mut RibHeap){
-        let x = pop_stack(stack, holder);
-        pop_stack(stack, holder);
-        push_stack(x, stack, holder);
+    fn rvm_arg2(stack: &mut Stack, holder: &mut RibHeap) -> Result<(), VmError>{
+        let x = stack.pop(holder)?;
+        stack.pop(holder)?;
+        stack.push(x);
+        Ok(())
     }
 
-    fn rvm_close(stack: &mut usize, holder: &mut RibHeap){
-        let f = pop_stack(stack,holder).get_rib(holder).first;
-        let m = RibField::Rib(*stack);
+    fn rvm_close(stack: &mut Stack, holder: &mut RibHeap) -> Result<(), VmError>{
+        let f = stack.pop(holder)?.get_rib(holder)?.first;
+        let m = RibField::rib(stack.spill(holder));
 
         let closure = holder.push_rib(
             make_data_rib(f,
@@ -828,35 +2461,62 @@ pub mod rvm {
                           PROCEDURE)
         );
 
-        push_stack(RibField::Rib(closure),
-                   stack, holder);
+        stack.push(RibField::rib(closure));
+        Ok(())
     }
 
-    fn list_tail(list: &usize, i:u32, holder: &mut RibHeap) ->usize{
-        if i==0 {*list} else {
-            list_tail(&holder.get(list).middle.get_rib_ref(),
+    // The RFS-0 conversions of strings are not features, the VM needs them
+    // for symbol names and error messages
+    fn str2scm(s: &str, holder: &mut RibHeap) -> RibField {
+        let mut chars = NIL_REF;
+        for c in s.chars().rev() {
+            push_stack(RibField::number(c as i64), &mut chars, holder);
+        }
+        RibField::rib(holder.push_rib(make_data_rib(RibField::rib(chars),
+                                                    RibField::number(s.chars().count() as i64),
+                                                    STRING)))
+    }
+
+    fn scm2str(s: &RibField, holder: &mut RibHeap) -> Result<String, VmError> {
+        if !is_rib(s) || s.get_rib(holder)?.last != RibField::number(STRING) {
+            return Err(VmError::TypeError(String::from("Expected a string")))
+        }
+        let string = s.get_rib(holder)?;
+        let mut result = String::new();
+        let mut chars = string.first;
+        while chars != RibField::rib(NIL_REF) {
+            let pair = chars.get_rib(holder)?;
+            result.push(to_char(pair.first.get_number()?)?);
+            chars = pair.middle;
+        }
+        Ok(result)
+    }
+
+    fn list_tail(list: &usize, i:u32, holder: &mut RibHeap) -> Result<usize, VmError>{
+        if i==0 {Ok(*list)} else {
+            list_tail(&holder.get(list).middle.get_rib_ref()?,
                       i-1, holder)
         }
     }
     // End of functions involving the stack
 
-    fn get_byte(iter: &mut Chars)-> u32 {
+    fn get_byte(iter: &mut Chars)-> Result<u32, VmError> {
         decode_char_to_u32(iter.next())
     }
 
-    fn get_code(iter: &mut Chars)-> i32 {
+    fn get_code(iter: &mut Chars)-> Result<i64, VmError> {
         // donne un nombre entre 0 et 92
         // Le bytecode de Ribbit n'utilise pas ' ' (ASCII 32), '"' (ASCII 34), et '/' (ASCII 47)
-        let x= get_byte(iter) as i32 -35 /*35: ASCII pour '#'*/ ;
-        if x<0 {57 /*57: ASCII pour '9'*/} else {x}
+        let x= get_byte(iter)? as i64 -35 /*35: ASCII pour '#'*/ ;
+        Ok(if x<0 {57 /*57: ASCII pour '9'*/} else {x})
     }
 
-    fn get_int(mut n:i32,iter:&mut Chars) -> i32 {
+    fn get_int(mut n:i64,iter:&mut Chars) -> Result<i64, VmError> {
 
-        let x=get_code(iter); // x entre 0 et 92 inclusif
+        let x=get_code(iter)?; // x entre 0 et 92 inclusif
         n *= 46; /* 46= 92/2, ASCII pour '.' */
         if x<46 {
-            n+x // n*46 + [0..45]
+            Ok(n+x) // n*46 + [0..45]
         } else {
             get_int(n+x-46,iter) // passe n*46 + [0..46] à get_int
         }
@@ -864,728 +2524,2294 @@ pub mod rvm {
 
 
 
-    fn symbol_ref(n: u32, symtbl:&usize, holder: &mut RibHeap)-> usize {
-        let tail_ref = list_tail(symtbl, n, holder);
+    fn symbol_ref(n: u32, symtbl:&usize, holder: &mut RibHeap)-> Result<usize, VmError> {
+        let tail_ref = list_tail(symtbl, n, holder)?;
         holder.get(&tail_ref).first.get_rib_ref()
     }
 
-    fn get_opnd_ref(o: &RibField, stack: &usize , holder: &mut RibHeap) -> usize {
-        match o {
-            RibField::Rib(ref r) => *r,
-            RibField::Number(ref n) => list_tail(stack, *n as u32, holder),
-        }
-    }
-
-    fn get_opnd(o: &RibField, stack: &usize , holder: &mut RibHeap) -> Rib {
-        let index = get_opnd_ref(o, stack, holder);
-        holder.get(&index)
-    }
-
-    fn get_cont(stack: &usize, holder: &mut RibHeap) -> usize {
+    fn get_cont(stack: &usize, holder: &mut RibHeap) -> Result<usize, VmError> {
         let mut s = *stack;
         let mut s_last = holder.get(&s).last;
         while !is_rib(&s_last) {
             let s_middle =holder.get(&s).middle;
-            s = s_middle.get_rib_ref();
+            s = s_middle.get_rib_ref()?;
             s_last = holder.get(&s).last;
         }
-        s
+        Ok(s)
     }
 
-    fn set_global(val_ref:usize,symtbl:&mut usize,holder: &mut RibHeap) {
+    fn set_global(val_ref:usize,symtbl:&mut usize,holder: &mut RibHeap) -> Result<(), VmError> {
         let sym_top = holder.get(symtbl);
-        let mut top_first = sym_top.first.get_rib(holder);
-        top_first.first = RibField::Rib(val_ref);
-        holder.set(&sym_top.first.get_rib_ref(), top_first);
-        *symtbl = sym_top.middle.get_rib_ref();
+        let mut top_first = sym_top.first.get_rib(holder)?;
+        top_first.first = RibField::rib(val_ref);
+        holder.set(&sym_top.first.get_rib_ref()?, top_first);
+        *symtbl = sym_top.middle.get_rib_ref()?;
+        Ok(())
     }
 
     // @@(feature arity-check
-    fn incoherent_nargs_stop(nargs:u32,expected_nargs:u32, variadic:bool) {
-        //TODO: Reformulate error message
-        if variadic {
-            eprintln!("Insufficient number of arguments. This function requires a minimum of {} arguments, got {}", expected_nargs, nargs);
-            println!("Insufficient number of arguments. This function requires a minimum of {} arguments, got {}", expected_nargs, nargs);
-        }
-        else {
-            eprintln!("Incorrect number of arguments. This function takes {} arguments, got {}", expected_nargs, nargs);
-            println!("Incorrect number of arguments. This function takes {} arguments, got {}", expected_nargs, nargs);
-        }
-        process::exit(0x0100)
+    fn incoherent_nargs_stop(nargs:u32,expected_nargs:u32, variadic:bool) -> VmError {
+        VmError::ArityMismatch { nargs, expected_nargs, variadic }
     }
     // )@@
 
-    pub fn run_rvm() {
 
-        let mut step_count:u32 =0;
-        let start_tracing:u32 = 0;
-        let mut next_stamp:u32 =0;
-        let mut tracing = true;
-        let heap_tracing = false;
-        let mut debug = true;
+    // Procedures of the library done by primitives, instead of loops of
+    // field0 and field1 calls. A primitive is included when the program
+    // uses the procedure, and the library then takes it in place of its own
+    // definition, as ##feature-<name> tells
 
+    // @@(feature rvm-eqv
+    fn rvm_eqv(x: &RibField, y: &RibField, holder: &mut RibHeap) -> Result<bool, VmError> {
+        Ok(x == y ||
+           (is_bignum(x, holder) && is_bignum(y, holder) &&
+            get_bigint(x, holder)? == get_bigint(y, holder)?) ||
+           (is_flonum(x, holder) && is_flonum(y, holder) &&
+            get_flonum(x, holder)? == get_flonum(y, holder)?))
+    }
+    // )@@
 
-        // @@(feature (not debug)
-        tracing = !tracing; // Pour enlever les warnings de rustc
-        debug = !debug;
-        // )@@
-
-        // @@(replace ");'lvD?m>lvRD?m>lvRA?m>lvRA?m>lvR:?m>lvR=!(:nlkm!':nlkv6{" (encode 92)
-        let rvm_code: String = ");'lvD?m>lvRD?m>lvRA?m>lvRA?m>lvR:?m>lvR=!(:nlkm!':nlkv6{".to_string();
-        // )@@
-
-        let mut pos = rvm_code.chars();
+    // Compares the fields in the order of the equal? of the library, with a
+    // stack of its own so that long lists do not overflow the Rust stack
+    // @@(feature rvm-equal (use rvm-eqv)
+    fn rvm_equal(x: &RibField, y: &RibField, holder: &mut RibHeap) -> Result<bool, VmError> {
+        let mut todo = Vec::new();
+        let (mut x, mut y) = (*x, *y);
+        loop {
+            if !rvm_eqv(&x, &y, holder)? {
+                if is_number(&x, holder) || is_number(&y, holder) {
+                    return Ok(false)
+                }
+                let a = x.get_rib(holder)?;
+                if a.last == RibField::number(SPECIAL) {
+                    return Ok(false)
+                }
+                let b = y.get_rib(holder)?;
+                todo.push((a.first, b.first));
+                todo.push((a.middle, b.middle));
+                todo.push((a.last, b.last));
+            }
+            match todo.pop() {
+                Some(next) => (x, y) = next,
+                None => return Ok(true),
+            }
+        }
+    }
+    // )@@
 
-        let mut rib_heap: RibHeap = RibHeap::with_capacity(rvm_code.len());
+    // @@(feature string-chars
+    fn string_chars(s: &RibField, holder: &mut RibHeap) -> Result<RibField, VmError> {
+        match s.as_rib() {
+            Some(string) if holder.get(&string).last == RibField::number(STRING) => Ok(holder.get(&string).first),
+            _ => Err(VmError::TypeError(String::from("Expected a string"))),
+        }
+    }
+    // )@@
 
-        rib_heap.push_rib(FALSE);
 
-        rib_heap.push_rib(TRUE);
+    // The codes of the built-in primitives, the numbers of the arms of the
+    // match of `primitives`
+    const PRIMITIVES: &[i64] = &[
+        // @@(primitives (gen index ", ")
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+        20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39,
+        40, 41, 42, 43, 44, 45, 46, 47, 48,
+        // )@@
+    ];
 
-        rib_heap.push_rib(NIL);
+    // The list primitive of arity-check and the primitives registered with
+    // Vm::register_primitive get the codes that follow, so that a built-in
+    // primitive missing above makes the arm of LIST_PRIMITIVE unreachable
+    const LIST_PRIMITIVE: i64 = PRIMITIVES.len() as i64;
+    const FIRST_HOST_PRIMITIVE: i64 = LIST_PRIMITIVE + 1;
 
-        let mut stack: usize;
+    type HostFunction = Box<dyn FnMut(&[RibField], &mut RibHeap) -> Result<RibField, VmError>>;
 
+    struct HostPrimitive {
+        name: String,
+        arity: u32,
+        function: HostFunction,
+    }
 
-        fn primitives(code:u8,
+    fn host_primitive(code: i64,
                       // @@(feature arity-check
                       expected_nargs: u32,
                       // )@@
-                      mut stack: &mut usize, mut rib_heap: &mut RibHeap) {
-            match code {
-                // @@(primitives (gen index " => " body)
-                0 => // @@(primitive (rib a b c)
-                    {
-                        rvm_prim3(
-                            // @@(feature arity-check
-                            expected_nargs,
-                            // )@@
-                            |z, y, x, h| -> RibField
-                            {
-                                RibField::Rib(
-                                    h.push_rib(
-                                        make_rib(x, y, z)
-                                    ))
-                            },
-                                  &mut stack, &mut rib_heap)
-                    },// )@@
-                1 => // @@(primitive (id x)
-                    { rvm_prim1(
-                        // @@(feature arity-check
-                        expected_nargs,
-                                 // )@@
-                                 |x,_h|x,&mut stack,&mut rib_heap) }, // )@@
-                2 => // @@(primitive (arg1 x y)
-                    {
-                        // @@(feature arity-check
-                        if expected_nargs != 2 {incoherent_nargs_stop(expected_nargs,2,false)};
-                        // )@@
-                        (||->(){ pop_stack(&mut stack, &mut rib_heap);})();}, // )@@
-                3 => // @@(primitive (arg2 x y)
-                    {
-                        // @@(feature arity-check
-                        if expected_nargs != 2 {incoherent_nargs_stop(expected_nargs,2,false)};
-                        // )@@
-                        rvm_arg2(&mut stack, &mut rib_heap)}, // )@@
-                4 => // @@(primitive (close rib)
-                    {
-                        // @@(feature arity-check
-                    if expected_nargs != 1 {incoherent_nargs_stop(expected_nargs, 1, false) };
-                        // )@@
-                        rvm_close(&mut stack, &mut rib_heap)
-                }, // )@@
-                5 => // @@(primitive (rib? rib) (use bool2scm)
-                    rvm_prim1(
-                        // @@(feature arity-check
-                        expected_nargs,
-                               // )@@
-                               |x, _h|
-                                   to_bool(||is_rib(&x)),
-                               &mut stack, &mut rib_heap), // )@@
-                6 => // @@(primitive (field0 rib)
-                    rvm_prim1(
-                        // @@(feature arity-check
-                        expected_nargs,
-                               // )@@
-                               |x, h|x.get_rib(h).first,
-                               &mut stack, &mut rib_heap), // )@@
-                7 => // @@(primitive (field1 rib)
-                    rvm_prim1(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                               |x, h|x.get_rib(h).middle,
-                               &mut stack, &mut rib_heap), // )@@
-                8 => // @@(primitive (field2 rib)
-                    rvm_prim1(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                               |x,h|x.get_rib(h).last,
-                               &mut stack, &mut rib_heap), // )@@
-                9 =>// @@(primitive (field0-set! rib)
-                    rvm_prim2(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                               |y,x, h|
-                                   {let mut new_rib = x.get_rib(h);
-                                       let x_index = x.get_rib_ref();
-                                       new_rib.first=y;
-                                       h.set(&x_index,new_rib);
-                                       y},
-                               &mut stack, &mut rib_heap), // )@@
-                10 => // @@(primitive (field1-set! rib)
-                    rvm_prim2(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                              |y,x, h|
-                                    {let mut new_rib = x.get_rib(h);
-                                        let x_index = x.get_rib_ref();
-                                        new_rib.middle=y;
-                                        h.set(&x_index,new_rib);
-                                        y},
-                                &mut stack, &mut rib_heap), // )@@
-                11 => // @@(primitive (field2-set! rib)
-                    rvm_prim2(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                               |y,x,h|
-                                    {let mut new_rib = x.get_rib(h);
-                                        let x_index = x.get_rib_ref();
-                                        new_rib.last=y;
-                                        h.set(&x_index,new_rib);
-                                        y},
-                                &mut stack, &mut rib_heap), // )@@
-                12 => // @@(primitive (eqv? rib1 rib2) (use bool2scm)
-                    rvm_prim2(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                               |y, x,_h|
-                                    { to_bool(||x==y)
-                                    }, &mut stack, &mut rib_heap), // )@@
-                13 => // @@(primitive (< x y) (use bool2scm)
-                    rvm_prim2(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                               |y, x,_h|
-                                    { to_bool(||x<y)
-                                    },
-                                &mut stack, &mut rib_heap), // )@@
-                14 => // @@(primitive (+ x y)
-                    rvm_prim2(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                               |y, x, _h|
-                                    { (x+y)
-                                        .expect("Addition operands should both be numbers")
-                                    },
-                                &mut stack, &mut rib_heap), // )@@
-                15 => // @@(primitive (- x y)
-                    rvm_prim2(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                               |y, x, _h|
-                                    { (x-y)
-                                        .expect("Subtraction operands should both be numbers")
-                                    },
-                                &mut stack, &mut rib_heap), // )@@
-                16 => // @@(primitive (* x y)
-                    rvm_prim2(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                        |y, x, _h|
-                                    { (x*y)
-                                        .expect("Factors should both be numbers")
-                                    },
-                                &mut stack, &mut rib_heap), // )@@
-                17 => // @@(primitive (quotient x y)
-                    rvm_prim2(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                               |y, x, _h|
-                                    { match y {
-                                        RibField::Number(0) => {println!("Division by zero");process::exit(1)}
-                                        _ => ()
-                                    };
-                                        (x/y)
-                                        .expect("Division operands should both be numbers")
-                                    },
-                                &mut stack, &mut rib_heap), // )@@
-                18 => // @@(primitive (getchar)
-                    {
-                    rvm_getchar(&mut stack, &mut rib_heap)
-                }, // )@@
-                19 => // @@(primitive (putchar c)
-                    rvm_prim1(
-                        // @@(feature arity-check
-                        expected_nargs,
-                        // )@@
-                               |x, _h| {
-                    let n_to_push = x.get_number() as u32;
-                    let c_to_write = std::char::from_u32(n_to_push)
-                        .expect(format!("expected representable character, got {}",n_to_push)
-                            .as_str());
-                    putchar(c_to_write);
-                    RibField::Number(n_to_push as i32)
-                },
-                                &mut stack, &mut rib_heap), // )@@
-                20 =>  // @@(primitive (exit n)
-                    rvm_prim1(
+                      host_primitives: &mut [HostPrimitive],
+                      stack: &mut Stack, holder: &mut RibHeap) -> Result<(), VmError> {
+        let primitive = match host_primitives.get_mut((code - FIRST_HOST_PRIMITIVE) as usize) {
+            Some(primitive) if code >= FIRST_HOST_PRIMITIVE => primitive,
+            _ => return Err(VmError::BadPrimitive(code)),
+        };
+        // @@(feature arity-check
+        if expected_nargs != primitive.arity {
+            return Err(incoherent_nargs_stop(expected_nargs, primitive.arity, false))
+        }
+        // )@@
+        let mut args = Vec::with_capacity(primitive.arity as usize);
+        for _ in 0..primitive.arity {
+            args.push(stack.pop(holder)?);
+        }
+        args.reverse();
+        let r = (primitive.function)(&args, holder)?;
+        stack.push(r);
+        Ok(())
+    }
+
+    fn primitives(code:i64,
+                  // @@(feature arity-check
+                  expected_nargs: u32,
+                  // )@@
+                  vm: &mut Vm) -> Result<(), VmError> {
+        let Vm { stack, rib_heap, input, output, host_primitives, .. } = vm;
+        match code {
+            // @@(primitives (gen index " => " body)
+            0 => // @@(primitive (rib a b c)
+                {
+                    rvm_prim3(
                         // @@(feature arity-check
                         expected_nargs,
                         // )@@
-                              |code, _h| {
-                    match code {
-                        RibField::Number(value) => process::exit(value),
-                        RibField::Rib(_) => process::exit(0x0100),
-                    }
-                },
-                                &mut stack, &mut rib_heap), // )@@
-                // @@(feature arity-check
-                21 => {
-                    let mut n_elems = expected_nargs;
-                    let mut elems = Vec::new();
-                    while n_elems > 0 {
-                        if !is_rib(&rib_heap.get(&stack).last) &&
-                            rib_heap.get(&stack).last.get_number() == 0
-                        {
-                            elems.push(pop_stack(&mut stack, &mut rib_heap));
-                            n_elems -= 1;
-                        }
-                        else
+                        |z, y, x, h| -> Result<RibField, VmError>
                         {
-                            eprintln!("Expected {} elements in the list but stack had {} elements",
-                            expected_nargs, elems.len());
-                            println!("Expected {} elements in the list but stack had {} elements",
-                            expected_nargs, elems.len());
-                            process::exit(0x0100)
-                        }
-                    }
-
-                    let mut new_list = NIL_REF;
-                    for e in elems {
-                        push_stack(e, &mut new_list, &mut rib_heap);
-                    };
-                    let new_vector = rib_heap.push_rib(make_data_rib(
-                        RibField::Rib(new_list),
-                        RibField::Number(expected_nargs as i32),
-                        4)
-                    );
-                    push_stack(RibField::Rib(new_vector),&mut stack, &mut rib_heap);
-                }, // )@@
-                // )@@
-                n => panic!("Unexpected code for primitive call {}",n),
-            }
-        }
+                            Ok(RibField::rib(
+                                h.push_rib(
+                                    make_rib(x, y, z)
+                                )))
+                        },
+                              stack, rib_heap)
+                },// )@@
+            1 => // @@(primitive (id x)
+                { rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                             // )@@
+                             |x,_h|Ok(x),stack,rib_heap) }, // )@@
+            2 => // @@(primitive (arg1 x y)
+                {
+                    // @@(feature arity-check
+                    if expected_nargs != 2 {return Err(incoherent_nargs_stop(expected_nargs,2,false))};
+                    // )@@
+                    stack.pop(rib_heap)?;
+                    Ok(())}, // )@@
+            3 => // @@(primitive (arg2 x y)
+                {
+                    // @@(feature arity-check
+                    if expected_nargs != 2 {return Err(incoherent_nargs_stop(expected_nargs,2,false))};
+                    // )@@
+                    rvm_arg2(stack, rib_heap)}, // )@@
+            4 => // @@(primitive (close rib)
+                {
+                    // @@(feature arity-check
+                if expected_nargs != 1 {return Err(incoherent_nargs_stop(expected_nargs, 1, false)) };
+                    // )@@
+                    rvm_close(stack, rib_heap)
+            }, // )@@
+            5 => // @@(primitive (rib? rib) (use bool2scm)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                           // )@@
+                           |x, h| {
+                               // Bignums and flonums are numbers, not ribs, as far as programs can tell
+                               let rib = !is_number(&x, h);
+                               Ok(to_bool(||rib))
+                           },
+                           stack, rib_heap), // )@@
+            6 => // @@(primitive (field0 rib)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                           // )@@
+                           |x, h|Ok(x.get_rib(h)?.first),
+                           stack, rib_heap), // )@@
+            7 => // @@(primitive (field1 rib)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                           |x, h|Ok(x.get_rib(h)?.middle),
+                           stack, rib_heap), // )@@
+            8 => // @@(primitive (field2 rib)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                           |x,h|Ok(x.get_rib(h)?.last),
+                           stack, rib_heap), // )@@
+            9 =>// @@(primitive (field0-set! rib)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                           |y,x, h|
+                               {let mut new_rib = x.get_rib(h)?;
+                                   let x_index = x.get_rib_ref()?;
+                                   new_rib.first=y;
+                                   h.set(&x_index,new_rib);
+                                   Ok(y)},
+                           stack, rib_heap), // )@@
+            10 => // @@(primitive (field1-set! rib)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                          |y,x, h|
+                                {let mut new_rib = x.get_rib(h)?;
+                                    let x_index = x.get_rib_ref()?;
+                                    new_rib.middle=y;
+                                    h.set(&x_index,new_rib);
+                                    Ok(y)},
+                            stack, rib_heap), // )@@
+            11 => // @@(primitive (field2-set! rib)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                           |y,x,h|
+                                {let mut new_rib = x.get_rib(h)?;
+                                    let x_index = x.get_rib_ref()?;
+                                    new_rib.last=y;
+                                    h.set(&x_index,new_rib);
+                                    Ok(y)},
+                            stack, rib_heap), // )@@
+            12 => // @@(primitive (eqv? rib1 rib2) (use bool2scm rvm-eqv)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                           |y, x, h|
+                                { let same = rvm_eqv(&x, &y, h)?;
+                                  Ok(to_bool(||same))
+                                }, stack, rib_heap), // )@@
+            13 => // @@(primitive (< x y) (use bool2scm)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                           |y, x, h|
+                                { let less = rvm_less(x, y, h)?;
+                                  Ok(to_bool(||less))
+                                },
+                            stack, rib_heap), // )@@
+            14 => // @@(primitive (+ x y)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                           |y, x, h|
+                                rvm_arith(x, y, h, i64::checked_add, BigInt::add, |a, b| a + b,
+                                          "Addition operands should both be numbers"),
+                            stack, rib_heap), // )@@
+            15 => // @@(primitive (- x y)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                           |y, x, h|
+                                rvm_arith(x, y, h, i64::checked_sub, BigInt::sub, |a, b| a - b,
+                                          "Subtraction operands should both be numbers"),
+                            stack, rib_heap), // )@@
+            16 => // @@(primitive (* x y)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |y, x, h|
+                                rvm_arith(x, y, h, i64::checked_mul, BigInt::mul, |a, b| a * b,
+                                          "Factors should both be numbers"),
+                            stack, rib_heap), // )@@
+            17 => // @@(primitive (quotient x y)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                           |y, x, h|
+                                { if is_zero(&y, h)? {
+                                    return Err(VmError::DivisionByZero)
+                                };
+                                  rvm_arith(x, y, h, i64::checked_div, BigInt::quotient,
+                                            |a, b| (a / b).trunc(),
+                                            "Division operands should both be numbers")
+                                },
+                            stack, rib_heap), // )@@
+            18 => // @@(primitive (getchar)
+                {
+                rvm_getchar(stack, input, output)
+            }, // )@@
+            19 => // @@(primitive (putchar c)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                           |x, _h| {
+                let n_to_push = x.get_number()?;
+                let c_to_write = to_char(n_to_push)?;
+                putchar(c_to_write, output)?;
+                Ok(RibField::number(n_to_push))
+            },
+                            stack, rib_heap), // )@@
+            20 =>  // @@(primitive (exit n)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                          |code, _h| {
+                flush_output(output)?;
+                match code.as_number() {
+                    Some(value) => Err(VmError::Exited(value as i32)),
+                    None => Err(VmError::Exited(0x0100)),
+                }
+            },
+                            stack, rib_heap), // )@@
+            21 => // @@(primitive (set-exception-handler! handler)
+                {
+                    // @@(feature arity-check
+                    if expected_nargs != 1 {return Err(incoherent_nargs_stop(expected_nargs,1,false))};
+                    // )@@
+                    let handler = stack.pop(rib_heap)?;
+                    if handler != RibField::rib(FALSE_REF) && handler_nparams(handler, rib_heap)?.is_none() {
+                        return Err(VmError::RejectedHandler(Box::new(VmError::TypeError(
+                            "Expected a procedure as exception handler".to_string()))))
+                    }
+                    vm.handler = handler.get_rib_ref()?;
+                    stack.push(handler);
+                    Ok(())}, // )@@
+            22 => // @@(primitive (flonum? x) (use bool2scm)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| {
+                        let flonum = is_flonum(&x, h);
+                        Ok(to_bool(||flonum))
+                    },
+                    stack, rib_heap), // )@@
+            23 => // @@(primitive (/ x y)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |y, x, h| {
+                        if !is_number(&x, h) || !is_number(&y, h) {
+                            return Err(VmError::TypeError(String::from("Division operands should both be numbers")))
+                        }
+                        if y == RibField::number(0) {
+                            return Err(VmError::DivisionByZero)
+                        }
+                        if !is_flonum(&x, h) && !is_flonum(&y, h) {
+                            // Stays exact when the division is exact
+                            let a = get_bigint(&x, h)?;
+                            let b = get_bigint(&y, h)?;
+                            let q = a.quotient(&b);
+                            if q.mul(&b) == a {
+                                return Ok(bigint_to_rib(q, h))
+                            }
+                        }
+                        let quotient = get_flonum(&x, h)? / get_flonum(&y, h)?;
+                        Ok(flonum_to_rib(quotient, h))
+                    },
+                    stack, rib_heap), // )@@
+            24 => // @@(primitive (exact->inexact x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| {
+                        if is_flonum(&x, h) {
+                            return Ok(x)
+                        }
+                        let f = get_flonum(&x, h)?;
+                        Ok(flonum_to_rib(f, h))
+                    },
+                    stack, rib_heap), // )@@
+            25 => // @@(primitive (inexact->exact x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| {
+                        if !is_flonum(&x, h) {
+                            if !is_number(&x, h) {
+                                return Err(VmError::TypeError(String::from("Conversion operand should be a number")))
+                            }
+                            return Ok(x)
+                        }
+                        let f = get_flonum(&x, h)?;
+                        if !f.is_finite() || f.fract() != 0.0 {
+                            return Err(VmError::TypeError(format!("Cannot convert {} to an exact integer", flonum_to_string(f))))
+                        }
+                        Ok(bigint_to_rib(BigInt::from_f64(f), h))
+                    },
+                    stack, rib_heap), // )@@
+            26 => // @@(primitive (flonum->string x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| {
+                        let f = get_flonum(&x, h)?;
+                        Ok(str2scm(&flonum_to_string(f), h))
+                    },
+                    stack, rib_heap), // )@@
+            27 => // @@(primitive (string->flonum str)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| {
+                        let s = scm2str(&x, h)?;
+                        Ok(match string_to_flonum(&s) {
+                            Some(f) => flonum_to_rib(f, h),
+                            None => RibField::rib(FALSE_REF),
+                        })
+                    },
+                    stack, rib_heap), // )@@
+            28 => // @@(primitive (floor x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_round(x, h, f64::floor),
+                    stack, rib_heap), // )@@
+            29 => // @@(primitive (ceiling x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_round(x, h, f64::ceil),
+                    stack, rib_heap), // )@@
+            30 => // @@(primitive (truncate x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_round(x, h, f64::trunc),
+                    stack, rib_heap), // )@@
+            31 => // @@(primitive (round x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_round(x, h, round_to_even),
+                    stack, rib_heap), // )@@
+            32 => // @@(primitive (sqrt x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_flonum_fn(x, h, f64::sqrt),
+                    stack, rib_heap), // )@@
+            33 => // @@(primitive (exp x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_flonum_fn(x, h, f64::exp),
+                    stack, rib_heap), // )@@
+            34 => // @@(primitive (log x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_flonum_fn(x, h, f64::ln),
+                    stack, rib_heap), // )@@
+            35 => // @@(primitive (sin x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_flonum_fn(x, h, f64::sin),
+                    stack, rib_heap), // )@@
+            36 => // @@(primitive (cos x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_flonum_fn(x, h, f64::cos),
+                    stack, rib_heap), // )@@
+            37 => // @@(primitive (tan x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_flonum_fn(x, h, f64::tan),
+                    stack, rib_heap), // )@@
+            38 => // @@(primitive (asin x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_flonum_fn(x, h, f64::asin),
+                    stack, rib_heap), // )@@
+            39 => // @@(primitive (acos x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_flonum_fn(x, h, f64::acos),
+                    stack, rib_heap), // )@@
+            40 => // @@(primitive (atan x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| rvm_flonum_fn(x, h, f64::atan),
+                    stack, rib_heap), // )@@
+            41 => // @@(primitive (flush-output)
+                {
+                    // @@(feature arity-check
+                    if expected_nargs != 0 {return Err(incoherent_nargs_stop(expected_nargs,0,false))};
+                    // )@@
+                    flush_output(output)?;
+                    stack.push(RibField::rib(TRUE_REF));
+                    Ok(())}, // )@@
+            42 => // @@(primitive (foreign? x) (use bool2scm)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| Ok(to_bool(|| is_foreign(&x, h))),
+                    stack, rib_heap), // )@@
+            43 => // @@(primitive (equal? x y) (use bool2scm rvm-equal)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |y, x, h| {
+                        let same = rvm_equal(&x, &y, h)?;
+                        Ok(to_bool(|| same))
+                    },
+                    stack, rib_heap), // )@@
+            44 => // @@(primitive (symbol-table-ref syms str) (use rvm-equal)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |str, syms, h| {
+                        let mut pair = syms;
+                        while pair != RibField::rib(NIL_REF) {
+                            let sym = pair.get_rib(h)?.first;
+                            if rvm_equal(&sym.get_rib(h)?.middle, &str, h)? {
+                                return Ok(sym)
+                            }
+                            pair = pair.get_rib(h)?.middle;
+                        }
+                        Ok(RibField::rib(FALSE_REF))
+                    },
+                    stack, rib_heap), // )@@
+            45 => // @@(primitive (list->string lst)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |lst, h| {
+                        // The string shares the list, like in the library
+                        let mut len = 0;
+                        let mut pair = lst;
+                        while pair != RibField::rib(NIL_REF) {
+                            let rib = pair.get_rib(h)?;
+                            if rib.last != RibField::number(PAIR) || is_rib(&rib.first) {
+                                return Err(VmError::TypeError(String::from("Expected a list of characters")))
+                            }
+                            len += 1;
+                            pair = rib.middle;
+                        }
+                        Ok(RibField::rib(h.push_rib(make_data_rib(lst, RibField::number(len), STRING))))
+                    },
+                    stack, rib_heap), // )@@
+            46 => // @@(primitive (string-append str1 str2) (use string-chars)
+                rvm_prim2(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |str2, str1, h| {
+                        // The characters of str2 are shared, like with append
+                        let mut list = string_chars(&str2, h)?.get_rib_ref()?;
+                        let mut chars = Vec::new();
+                        let mut pair = string_chars(&str1, h)?;
+                        while pair != RibField::rib(NIL_REF) {
+                            let rib = pair.get_rib(h)?;
+                            chars.push(rib.first);
+                            pair = rib.middle;
+                        }
+                        let len = str1.get_rib(h)?.middle.get_number()? + str2.get_rib(h)?.middle.get_number()?;
+                        for c in chars.into_iter().rev() {
+                            push_stack(c, &mut list, h);
+                        }
+                        Ok(RibField::rib(h.push_rib(make_data_rib(RibField::rib(list), RibField::number(len), STRING))))
+                    },
+                    stack, rib_heap), // )@@
+            47 => // @@(primitive (number->string x)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |x, h| {
+                        let s = match x.as_number() {
+                            Some(n) => n.to_string(),
+                            None if is_flonum(&x, h) => flonum_to_string(get_flonum(&x, h)?),
+                            None if is_bignum(&x, h) => get_bigint(&x, h)?.to_string(),
+                            None => return Err(VmError::TypeError(String::from("Expected a number"))),
+                        };
+                        Ok(str2scm(&s, h))
+                    },
+                    stack, rib_heap), // )@@
+            48 => // @@(primitive (write-string str) (use string-chars)
+                rvm_prim1(
+                    // @@(feature arity-check
+                    expected_nargs,
+                    // )@@
+                    |str, h| {
+                        let mut pair = string_chars(&str, h)?;
+                        while pair != RibField::rib(NIL_REF) {
+                            let rib = pair.get_rib(h)?;
+                            putchar(to_char(rib.first.get_number()?)?, output)?;
+                            pair = rib.middle;
+                        }
+                        Ok(str)
+                    },
+                    stack, rib_heap), // )@@
+            // @@(feature arity-check
+            LIST_PRIMITIVE => {
+                let mut n_elems = expected_nargs;
+                let mut elems = Vec::new();
+                while n_elems > 0 {
+                    // The elements in the buffer are values, not frames
+                    if !stack.top.is_empty() ||
+                        !is_rib(&rib_heap.get(&stack.ribs).last) &&
+                        rib_heap.get(&stack.ribs).last.get_number()? == 0
+                    {
+                        elems.push(stack.pop(rib_heap)?);
+                        n_elems -= 1;
+                    }
+                    else
+                    {
+                        return Err(VmError::TypeError(format!(
+                            "Expected {} elements in the list but stack had {} elements",
+                            expected_nargs, elems.len())))
+                    }
+                }
 
-        // Build the initial symbol table
+                let mut new_list = NIL_REF;
+                for e in elems {
+                    push_stack(e, &mut new_list, rib_heap);
+                };
+                let new_vector = rib_heap.push_rib(make_data_rib(
+                    RibField::rib(new_list),
+                    RibField::number(expected_nargs as i64),
+                    4)
+                );
+                stack.push(RibField::rib(new_vector));
+                Ok(())
+            }, // )@@
+            // )@@
+            n => host_primitive(n,
+                                // @@(feature arity-check
+                                expected_nargs,
+                                // )@@
+                                host_primitives, stack, rib_heap),
+        }
+    }
 
-        let mut symtbl = NIL_REF;
-        let mut n = get_int(0,&mut pos);
-        // n = rvm_code[0]>=35?(rvm_code[0] -35), 57
-        while n>0 /*si rvm_code[0]=='#', la boucle est skipped*/
-        {
-            //Ceci alloue des structures SYMBOL vides (noms= "", value= FALSE
-            n -= 1;
-            let inner = rib_heap.push_rib(make_data_rib(
-                RibField::Rib(NIL_REF),
-                RibField::Number(0),
-                STRING));
-            let outer = rib_heap.push_rib(make_data_rib(
-                RibField::Rib(FALSE_REF),
-                RibField::Rib(inner),
-                SYMBOL,
-            ));
-            symtbl = rib_heap.push_rib(make_data_rib(
-                RibField::Rib(outer),
-                RibField::Rib(symtbl),
-                PAIR
-            ));
+    /// How a call to `Vm::run_for` ended, when it did not fail.
+    #[derive(Debug,Clone,Copy,PartialEq,Eq)]
+    pub enum RunStatus {
+        /// The program executed its final `HALT`.
+        Halted,
+        /// The instruction budget ran out before the program halted.
+        OutOfFuel,
+        /// The program called `exit` with this status. It cannot be resumed.
+        Exited(i32),
+    }
+
+    /// Returns the number of parameters of `handler`, encoded as in the
+    /// code of closures, when it is a procedure that can be called with one
+    /// argument, and `None` otherwise.
+    fn handler_nparams(handler: RibField, rib_heap: &mut RibHeap) -> Result<Option<i64>, VmError> {
+        let handler_rib = match handler.as_rib() {
+            Some(h) => rib_heap.get(&h),
+            None => return Ok(None),
+        };
+        if handler_rib.last != RibField::number(PROCEDURE) {
+            return Ok(None)
+        }
+        let nparams = match handler_rib.first.as_rib() {
+            Some(c) => rib_heap.get(&c).first.get_number()?,
+            None => -1, // a primitive
         };
+        let variadic = nparams % 2 == 1;
+        Ok(if nparams == 2 || variadic && nparams >> 1 <= 1 { Some(nparams) } else { None })
+    }
 
+    /// A Ribbit virtual machine.
+    ///
+    /// The VM owns its rib heap along with the registers of the interpreter:
+    /// the stack, the program counter and the symbol table. A program is
+    /// given to the VM in its compacted RVM code form with `load`, then
+    /// executed with `run`. Programs read standard input and write standard
+    /// output unless given other streams with `set_input` and `set_output`.
+    pub struct Vm {
+        rib_heap: RibHeap,
+        stack: Stack,
+        pc: usize,
+        symtbl: usize,
+        handler: usize,
+        raised: Option<VmError>,
+        host_primitives: Vec<HostPrimitive>,
+        native: Vec<NativeBlock>,
+        native_roots: Option<(Handle, usize)>, // a list of native_starts then native_operands, and where it was
+        native_starts: Vec<usize>, // the first operation of each native block
+        native_operands: Vec<RibField>, // the rib operands of the native blocks
+        input: Box<dyn BufRead>,
+        output: BufWriter<Output>,
+        size_of_heap: usize,
+        heap_growth: usize,
+        incremental_gc: bool,
+        gc_count: u32,
+        step_count: u32,
+        start_tracing: u32,
+        next_stamp: u32,
+        tracing: bool,
+        heap_tracing: bool,
+        debug: bool,
+    }
+
+    impl Default for Vm {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Vm {
+        /// Creates a VM with an empty heap. Running it before a program
+        /// is loaded halts immediately.
+        pub fn new() -> Self {
+            let mut tracing = true;
+            let mut debug = true;
 
-        let mut accum = NIL_REF;
-        let mut n=0;
-        loop{
-            let c = get_byte(&mut pos); // 1e iteration: c = rvm_code[1]
-            if c==44 /*44: ASCII pour ','*/ {
+            // @@(feature (not debug)
+            tracing = !tracing; // Pour enlever les warnings de rustc
+            debug = !debug;
+            // )@@
+
+            let mut vm = Vm {
+                rib_heap: RibHeap::with_capacity(0),
+                stack: Stack::new(NIL_REF),
+                pc: NIL_REF,
+                symtbl: NIL_REF,
+                handler: FALSE_REF,
+                raised: None,
+                host_primitives: Vec::new(),
+                native: Vec::new(),
+                native_roots: None,
+                native_starts: Vec::new(),
+                native_operands: Vec::new(),
+                input: Box::new(BufReader::new(stdin())),
+                output: BufWriter::new(Output::Writer(Box::new(stdout()))),
+                size_of_heap: 0,
+                heap_growth: 100,
+                incremental_gc: false,
+                gc_count: 0,
+                step_count: 0,
+                start_tracing: 0,
+                next_stamp: 0,
+                tracing,
+                heap_tracing: false,
+                debug,
+            };
+            vm.reset();
+            vm
+        }
+
+        /// Discards the loaded program and every object on the heap, rooted
+        /// values included, bringing the VM back to the state it had when
+        /// created.
+        pub fn reset(&mut self) {
+            let max_size = self.rib_heap.max_size;
+            self.rib_heap = RibHeap::with_capacity(3);
+            self.rib_heap.max_size = max_size;
+
+            self.rib_heap.push_rib(FALSE);
+
+            self.rib_heap.push_rib(TRUE);
+
+            self.rib_heap.push_rib(NIL);
+
+            self.pc = self.rib_heap.push_rib(make_op_rib(HALT,
+                                                         RibField::number(0),
+                                                         RibField::number(0)));
+            self.stack.reset(NIL_REF);
+            self.native.clear();
+            self.native_roots = None;
+            self.native_starts.clear();
+            self.native_operands.clear();
+            self.symtbl = NIL_REF;
+            self.handler = FALSE_REF;
+            self.raised = None;
+            self.size_of_heap = self.rib_heap.heap.len();
+            self.gc_count = 0;
+            self.step_count = 0;
+            self.next_stamp = 0;
+        }
+
+        /// Decodes compacted RVM code and prepares the VM to execute it.
+        /// The previously loaded program, if any, becomes garbage along with
+        /// its exception handler and the error it was handling. Malformed
+        /// code is reported as an error and leaves the registers of the VM
+        /// untouched.
+        ///
+        /// The code must pass the number of arguments of each call, as
+        /// rsc does with the arity-check feature, and use the primitives
+        /// with the numbers of this file. Code compiled for the rvm target
+        /// (`rsc -t rvm -f+ arity-check`) does, as it only uses the 21
+        /// primitives every host has; the code rsc embeds in a host it
+        /// generates with `-t rs` numbers the primitives it uses in another
+        /// order.
+        pub fn load(&mut self, rvm_code: &str) -> Result<(), VmError> {
+
+            let mut pos = rvm_code.chars();
+
+            self.rib_heap.heap.reserve(rvm_code.len());
+
+            let rib_heap = &mut self.rib_heap;
+
+            let mut stack: usize;
+
+            // Build the initial symbol table
+
+            let mut symtbl = NIL_REF;
+            let mut n = get_int(0,&mut pos)?;
+            // n = rvm_code[0]>=35?(rvm_code[0] -35), 57
+            while n>0 /*si rvm_code[0]=='#', la boucle est skipped*/
+            {
+                //Ceci alloue des structures SYMBOL vides (noms= "", value= FALSE
+                n -= 1;
                 let inner = rib_heap.push_rib(make_data_rib(
-                    RibField::Rib(accum),
-                    RibField::Number(n),
-                    STRING
-                ));
+                    RibField::rib(NIL_REF),
+                    RibField::number(0),
+                    STRING));
                 let outer = rib_heap.push_rib(make_data_rib(
-                    RibField::Rib(FALSE_REF),
-                    RibField::Rib(inner),
-                    SYMBOL
+                    RibField::rib(FALSE_REF),
+                    RibField::rib(inner),
+                    SYMBOL,
                 ));
                 symtbl = rib_heap.push_rib(make_data_rib(
-                    RibField::Rib(outer),
-                    RibField::Rib(symtbl),
+                    RibField::rib(outer),
+                    RibField::rib(symtbl),
                     PAIR
                 ));
-                accum=NIL_REF;
-                n=0;
-            } else {
-                if c==59 /*ASCII pour ';'*/ {break};
-                let ch = c as i32;
-                push_stack(RibField::Number(ch),&mut accum,&mut rib_heap);
-                n+=1;
+            };
+
+
+            let mut accum = NIL_REF;
+            let mut n=0;
+            loop{
+                let c = get_byte(&mut pos)?; // 1e iteration: c = rvm_code[1]
+                if c==44 /*44: ASCII pour ','*/ {
+                    let inner = rib_heap.push_rib(make_data_rib(
+                        RibField::rib(accum),
+                        RibField::number(n),
+                        STRING
+                    ));
+                    let outer = rib_heap.push_rib(make_data_rib(
+                        RibField::rib(FALSE_REF),
+                        RibField::rib(inner),
+                        SYMBOL
+                    ));
+                    symtbl = rib_heap.push_rib(make_data_rib(
+                        RibField::rib(outer),
+                        RibField::rib(symtbl),
+                        PAIR
+                    ));
+                    accum=NIL_REF;
+                    n=0;
+                } else {
+                    if c==59 /*ASCII pour ';'*/ {break};
+                    let ch = c as i64;
+                    push_stack(RibField::number(ch),&mut accum,rib_heap);
+                    n+=1;
+                }
             }
+
+            let inner = rib_heap.push_rib(make_data_rib(
+                RibField::rib(accum),
+                RibField::number(n),
+                STRING
+            ));
+            let outer = rib_heap.push_rib(make_data_rib(
+                RibField::rib(FALSE_REF),
+                RibField::rib(inner),
+                SYMBOL
+            ));
+            symtbl = rib_heap.push_rib(make_data_rib(
+                RibField::rib(outer),
+                RibField::rib(symtbl),
+                PAIR
+            ));
+
+
+
+            // Les procédures n'ont pas encore été construites ni assignées aux entrées de la symtbl
+
+            // Decode the RVM instructions
+
+            let mut n_field:RibField;
+
+            stack = rib_heap.push_rib(make_data_rib(RibField::number(6),RibField::number(6),6));
+
+            loop {
+                let x = get_code(&mut pos)?; //1e iteration: 1e char après ';' dans rvm_code
+                let mut n = x; // 0<=n<=92
+                let mut d ;
+                let mut op = CALL;
+                loop{
+                    //
+                    // x<=22:op=CALL,  ??23=<x<=55:op=SET,
+                    // ??56=<x<=57:op=GET, ??58=<x<=60:op=CNST,
+                    // ??61<=x<=74:op=IF, ??75=<x<=81:op=HALT
+                    // 82<=x<=92 ???
+                    d = match op {
+                        CALL => 20,
+                        SET=> 30,
+                        GET=> 0,
+                        CNST=> 10,
+                        IF=> 11,
+                        HALT=> 4,
+                        _ => return Err(VmError::BadInstruction(op))
+                    };
+                    if n<= d+2 {break};
+                    n-=d+3;
+                    op+=1;
+
+                };
+                if x>90 {
+                    n_field=pop_stack(&mut stack,rib_heap)?;
+                } else {
+                    if op==CALL {
+                        push_stack(RibField::number(0),&mut stack, rib_heap);
+                        op+=1;
+                    };
+                    if n>=d { //n= d+2, d+1, ou d
+                        if n==d {
+                            n_field = RibField::number(get_int(0,&mut pos)?);
+                        } else {
+                            n_field = RibField::rib(symbol_ref(get_int(n-d-1,&mut pos)? as u32, // n-d-1= 1, 0
+                                                               &symtbl,rib_heap)?);
+                        }
+                    } else { // n < d
+                        if op<CNST { //CALL, SET, GET
+                            n_field = RibField::rib(symbol_ref(n as u32,&symtbl,rib_heap)?);
+                        } else { //CNST, IF, HALT
+                            n_field = RibField::number(n);
+
+                        }
+                    };
+                    if op>IF {
+                        let popped = pop_stack(&mut stack,rib_heap)?;
+                        let inner = rib_heap.push_rib(make_rib(
+                            n_field,
+                            RibField::number(0),
+                            popped
+                        ));
+                        n_field = RibField::rib(rib_heap.push_rib(make_data_rib(
+                            RibField::rib(inner),
+                            RibField::rib(NIL_REF),
+                            PROCEDURE
+                        )));
+                        if !is_rib(&rib_heap.get(&stack).middle) {break};
+                        op = IF;
+                    };
+                };
+
+                // Il ne fait que push des n0, ils sont modifiés ici
+                let stack_first= rib_heap.get(&stack).first;
+                let new_rib_ref = rib_heap.push_rib(
+                    make_op_rib(
+                        op-1,
+                        n_field,
+                        stack_first
+                    ));
+                let mut top_stack = rib_heap.get(&stack);
+                top_stack.first = RibField::rib(new_rib_ref);
+                rib_heap.set(&stack, top_stack); // <- Là, spécifiquement
+            };
+
+
+            let n_first = n_field.get_rib(rib_heap)?.first;
+            let pc: RibField = n_first.get_rib(rib_heap)?.last;
+
+
+            set_global(rib_heap.push_rib(make_data_rib(RibField::number(0),
+                                                       RibField::rib(symtbl),
+                                                       PROCEDURE)),
+                       &mut symtbl, rib_heap)?;
+            set_global(FALSE_REF,
+                       &mut symtbl, rib_heap)?;
+            set_global(TRUE_REF,
+                       &mut symtbl, rib_heap)?;
+            set_global(NIL_REF,
+                       &mut symtbl, rib_heap)?;
+
+            // Il faut assigner le symbole "list" à la primitive list, si elle est présente
+
+
+
+            let halt_instr = rib_heap.push_rib(make_op_rib(HALT,
+                                                           RibField::number(0),
+                                                           RibField::number(0)));
+
+            let primordial_cont = make_op_rib(CALL,
+                                              RibField::number(0),
+                                              RibField::rib(halt_instr));
+
+            stack = rib_heap.push_rib(primordial_cont);
+
+
+            // @@(feature debug
+            if self.tracing {
+                eprintln!("{}",show(&pc,rib_heap)?);
+            }
+            // )@@
+
+            self.clear_native_code()?;
+            self.stack.reset(stack);
+            self.pc = pc.get_rib_ref()?;
+            self.symtbl = symtbl;
+            self.handler = FALSE_REF;
+            self.raised = None;
+
+            self.size_of_heap = self.rib_heap.heap.len();
+            if self.heap_tracing {
+                eprintln!("Heap size before first gc: {}", self.size_of_heap);
+            }
+
+            self.size_of_heap = self.rib_heap.garbage_collect(&mut self.stack.ribs, &mut self.pc, &mut self.symtbl, &mut self.handler);
+
+            if self.heap_tracing {
+                eprintln!("Heap size after first gc: {}", self.size_of_heap);
+            }
+
+            self.gc_count = 1;
+
+            for i in 0..self.host_primitives.len() {
+                self.bind_host_primitive(i)?;
+            }
+            Ok(())
+        }
+
+        /// Executes the loaded program until it halts. An error is first
+        /// delivered to the exception handler of the program, if it has one,
+        /// and otherwise stops the execution and is returned to the caller.
+        /// The output of the program is flushed in both cases. The status is
+        /// `RunStatus::Halted`, or `RunStatus::Exited` when the program
+        /// called `exit`.
+        pub fn run(&mut self) -> Result<RunStatus, VmError> {
+            loop {
+                match self.run_for(u64::MAX)? {
+                    RunStatus::OutOfFuel => (),
+                    status => return Ok(status),
+                }
+            }
+        }
+
+        /// Like `run`, but executes at most `fuel` instructions. When they
+        /// are used up before the program halts, the output is flushed and
+        /// `RunStatus::OutOfFuel` is returned; the VM is left as is, so a
+        /// later call to `run_for` or `run` resumes the program.
+        pub fn run_for(&mut self, fuel: u64) -> Result<RunStatus, VmError> {
+
+            let mut executed = 0;
+            while executed < fuel {
+                // A native block runs as a whole, or its operations are
+                // interpreted
+                let block = match self.rib_heap.code.native_block(self.pc) {
+                    Some(b) if u64::from(self.native[b].len) <= fuel - executed => Some(b),
+                    _ => None,
+                };
+                let result = match block {
+                    Some(b) => {
+                        // The block goes on with the blocks that follow it
+                        // while the fuel lasts
+                        let fuel_left = fuel - executed - u64::from(self.native[b].len);
+                        let (result, fuel_left) = Native::run(self, fuel_left, b);
+                        executed = fuel - fuel_left;
+                        result
+                    },
+                    None => {
+                        executed += 1;
+                        self.step()
+                    },
+                };
+                match result {
+                    Ok(true) => {
+                        self.flush()?;
+                        return match self.raised.take() {
+                            Some(err) => Err(err), // the exception handler returned
+                            None => Ok(RunStatus::Halted),
+                        }
+                    },
+                    Ok(false) => (),
+                    // exit is not an error of the program, it is not handled
+                    Err(VmError::Exited(status)) => {
+                        self.flush()?;
+                        return Ok(RunStatus::Exited(status))
+                    },
+                    Err(err) => if let Err(err) = self.raise(err) {
+                        self.flush().ok(); // the error of the program comes first
+                        return Err(err)
+                    },
+                }
+
+                // A full nursery is collected by a minor collection, unless the
+                // old generation grew by heap_growth percent since the last
+                // major collection
+                let nursery_size = NURSERY_SIZE.min(self.rib_heap.max_size / 4);
+                if self.rib_heap.nursery_len() > nursery_size {
+                    self.gc_count += 1;
+
+                    // @@(feature debug
+                    if self.heap_tracing {
+                        eprintln!("Heap size before {}th gc: {}", self.gc_count, self.rib_heap.heap.len());
+                    }
+                    // )@@
+                    self.stack.spill(&mut self.rib_heap);
+                    let growth = self.rib_heap.old_len.saturating_sub(self.size_of_heap);
+                    if growth <= self.size_of_heap.saturating_mul(self.heap_growth) / 100 {
+                        self.rib_heap.minor_collect(&mut self.stack.ribs,&mut self.pc, &mut self.symtbl, &mut self.handler);
+                    } else if self.incremental_gc {
+                        self.rib_heap.start_incremental_collect(&mut self.stack.ribs,&mut self.pc, &mut self.symtbl, &mut self.handler);
+                    } else {
+                        self.size_of_heap = self.rib_heap.garbage_collect(&mut self.stack.ribs,&mut self.pc, &mut self.symtbl, &mut self.handler);
+                    }
+
+                    // The next nursery must fit below the maximum size of the
+                    // heap. The program may handle the error until the next
+                    // collection, which raises it again if needed.
+                    if self.rib_heap.heap.len() + nursery_size > self.rib_heap.max_size {
+                        self.size_of_heap = self.rib_heap.garbage_collect(&mut self.stack.ribs,&mut self.pc, &mut self.symtbl, &mut self.handler);
+                        if self.size_of_heap + nursery_size > self.rib_heap.max_size {
+                            if let Err(err) = self.raise(VmError::HeapExhausted) {
+                                self.flush().ok();
+                                return Err(err)
+                            }
+                        }
+                    }
+                    // @@(feature debug
+                    if self.heap_tracing {
+                        eprintln!("Heap size after {}th gc: {}", self.gc_count, self.rib_heap.heap.len());
+                    }
+                    // )@@
+                    self.map_native_code()?;
+                }
+
+                if self.rib_heap.step_collect() {
+                    self.stack.spill(&mut self.rib_heap);
+                    self.size_of_heap = self.rib_heap.finish_incremental_collect(&mut self.stack.ribs,&mut self.pc, &mut self.symtbl, &mut self.handler);
+                    self.map_native_code()?;
+                }
+            }
+
+            self.flush()?;
+            Ok(RunStatus::OutOfFuel)
+        }
+
+        /// The heap of the VM, to build the arguments of `call` and inspect
+        /// its result, for example with `ToScheme` and `FromScheme`.
+        pub fn heap(&mut self) -> &mut RibHeap {
+            &mut self.rib_heap
+        }
+
+        /// The index in the heap of the operation the program executes
+        /// next, where `run_for` or an error left it.
+        pub fn pc(&self) -> usize {
+            self.pc
+        }
+
+        /// The values on the stack of the running procedure, the top first,
+        /// down to the frame of its caller.
+        pub fn stack(&self) -> Vec<RibField> {
+            let mut values: Vec<RibField> = self.stack.top.iter().rev().copied().collect();
+            let mut s = self.stack.ribs;
+            while s != NIL_REF && !is_rib(&self.rib_heap[s].last) {
+                values.push(self.rib_heap[s].first);
+                // The stack of the primordial continuation ends with a number
+                match self.rib_heap[s].middle.as_rib() {
+                    Some(next) => s = next,
+                    None => break,
+                }
+            }
+            values
+        }
+
+        /// Keeps `value` alive across garbage collections, see
+        /// `RibHeap::root`. Handles survive `load` but not `reset`.
+        pub fn root(&mut self, value: RibField) -> Handle {
+            self.rib_heap.root(value)
+        }
+
+        /// Returns the current value of a rooted value, see
+        /// `RibHeap::rooted`.
+        pub fn rooted(&self, handle: &Handle) -> Result<RibField, VmError> {
+            self.rib_heap.rooted(handle)
         }
 
-        let inner = rib_heap.push_rib(make_data_rib(
-            RibField::Rib(accum),
-            RibField::Number(n),
-            STRING
-        ));
-        let outer = rib_heap.push_rib(make_data_rib(
-            RibField::Rib(FALSE_REF),
-            RibField::Rib(inner),
-            SYMBOL
-        ));
-        symtbl = rib_heap.push_rib(make_data_rib(
-            RibField::Rib(outer),
-            RibField::Rib(symtbl),
-            PAIR
-        ));
+        /// Releases a rooted value, see `RibHeap::unroot`.
+        pub fn unroot(&mut self, handle: Handle) -> Result<RibField, VmError> {
+            self.rib_heap.unroot(handle)
+        }
 
+        /// Makes the Rust function `function` available to programs as the
+        /// primitive procedure `name`, which takes `arity` arguments. The
+        /// function receives the arguments in order along with the heap, and
+        /// returns the result of the call or an error that is raised in the
+        /// program like those of the built-in primitives.
+        ///
+        /// The global variable `name` of the loaded program, and of those
+        /// loaded later, is bound to the primitive; programs that export some
+        /// of their symbols must export `name` for it to be found. Registering
+        /// `name` again replaces its function.
+        pub fn register_primitive<F>(&mut self, name: &str, arity: u32, function: F) -> Result<(), VmError>
+            where F: FnMut(&[RibField], &mut RibHeap) -> Result<RibField, VmError> + 'static {
+            let primitive = HostPrimitive {
+                name: String::from(name),
+                arity,
+                function: Box::new(function),
+            };
+            match self.host_primitives.iter().position(|p| p.name == name) {
+                Some(i) => {
+                    self.host_primitives[i] = primitive;
+                    self.bind_host_primitive(i)
+                },
+                None => {
+                    self.host_primitives.push(primitive);
+                    self.bind_host_primitive(self.host_primitives.len() - 1)
+                },
+            }
+        }
 
+        // Sets the global variable named after the ith host primitive
+        fn bind_host_primitive(&mut self, i: usize) -> Result<(), VmError> {
+            if self.symtbl == NIL_REF {
+                return Ok(()) // no program with symbols of its own, see load
+            }
+            let code = FIRST_HOST_PRIMITIVE + i as i64;
+            let procedure = self.rib_heap.push_rib(make_data_rib(RibField::number(code),
+                                                                 RibField::number(0),
+                                                                 PROCEDURE));
+            let name = self.host_primitives[i].name.clone();
+            self.set_global(&name, RibField::rib(procedure))
+        }
 
-        // Les procédures n'ont pas encore été construites ni assignées aux entrées de la symtbl
+        // The symbol named `name` in the symbol table of the loaded program
+        fn find_symbol(&mut self, name: &str) -> Result<Option<usize>, VmError> {
+            let mut pair = self.symtbl;
+            while pair != NIL_REF {
+                let sym = self.rib_heap.get(&pair).first.get_rib_ref()?;
+                if scm2str(&self.rib_heap.get(&sym).middle, &mut self.rib_heap)? == name {
+                    return Ok(Some(sym))
+                }
+                pair = self.rib_heap.get(&pair).middle.get_rib_ref()?;
+            }
+            Ok(None)
+        }
 
-        // Decode the RVM instructions
+        // Like find_symbol, but a missing symbol is added at the end of the
+        // symbol table, where string->symbol still finds it. The table must
+        // not be empty.
+        fn intern_symbol(&mut self, name: &str) -> Result<usize, VmError> {
+            if let Some(sym) = self.find_symbol(name)? {
+                return Ok(sym)
+            }
+            let rib_heap = &mut self.rib_heap;
+            let sym_name = str2scm(name, rib_heap);
+            let sym = rib_heap.push_rib(make_data_rib(RibField::rib(FALSE_REF),
+                                                      sym_name,
+                                                      SYMBOL));
+            let new_pair = rib_heap.push_rib(make_data_rib(RibField::rib(sym),
+                                                           RibField::rib(NIL_REF),
+                                                           PAIR));
+            let mut pair = self.symtbl;
+            let mut last_pair = rib_heap.get(&pair);
+            while last_pair.middle != RibField::rib(NIL_REF) {
+                pair = last_pair.middle.get_rib_ref()?;
+                last_pair = rib_heap.get(&pair);
+            }
+            last_pair.middle = RibField::rib(new_pair);
+            rib_heap.set(&pair, last_pair);
+            Ok(sym)
+        }
 
-        let mut n_field:RibField;
+        // @@(feature rib (use bind-exports)
+        // The host looks globals up by name, so rsc binds the exported
+        // primitives of the program even when it does not use them
+        // )@@
 
-        stack = rib_heap.push_rib(make_data_rib(RibField::Number(6),RibField::Number(6),6));
+        /// Returns the value of the global variable `name` of the loaded
+        /// program. Programs that export some of their symbols only have
+        /// those by name. The value is only valid until the next garbage
+        /// collection, see `RibHeap`.
+        pub fn get_global(&mut self, name: &str) -> Result<RibField, VmError> {
+            match self.find_symbol(name)? {
+                Some(sym) => Ok(self.rib_heap.get(&sym).first),
+                None => Err(VmError::UnknownGlobal(String::from(name))),
+            }
+        }
 
-        loop {
-            let x = get_code(&mut pos); //1e iteration: 1e char après ';' dans rvm_code
-            let mut n = x; // 0<=n<=92
-            let mut d ;
-            let mut op = CALL;
-            loop{
-                //
-                // x<=22:op=CALL,  ??23=<x<=55:op=SET,
-                // ??56=<x<=57:op=GET, ??58=<x<=60:op=CNST,
-                // ??61<=x<=74:op=IF, ??75=<x<=81:op=HALT
-                // 82<=x<=92 ???
-                d = match op {
-                    CALL => 20,
-                    SET=> 30,
-                    GET=> 0,
-                    CNST=> 10,
-                    IF=> 11,
-                    HALT=> 4,
-                    _ => panic!("Unexpected op value {}",op)
-                };
-                if n<= d+2 {break};
-                n-=d+3;
-                op+=1;
+        /// Sets the global variable `name` of the loaded program to `value`,
+        /// for example before running it so that it reads `value`. A global
+        /// the program does not have is added to its symbol table, where
+        /// `string->symbol` and `eval` find it.
+        pub fn set_global(&mut self, name: &str, value: RibField) -> Result<(), VmError> {
+            if self.symtbl == NIL_REF {
+                return Err(VmError::UnknownGlobal(String::from(name)))
+            }
+            let sym = self.intern_symbol(name)?;
+            let mut sym_rib = self.rib_heap.get(&sym);
+            sym_rib.first = value;
+            self.rib_heap.set(&sym, sym_rib);
+            Ok(())
+        }
 
-            };
-            if x>90 {
-                n_field=pop_stack(&mut stack,&mut rib_heap);
-            } else {
-                if op==CALL {
-                    push_stack(RibField::Number(0),&mut stack, &mut rib_heap);
-                    op+=1;
-                };
-                if n>=d { //n= d+2, d+1, ou d
-                    if n==d {
-                        n_field = RibField::Number(get_int(0,&mut pos));
-                    } else {
-                        n_field = RibField::Rib(symbol_ref(get_int(n-d-1,&mut pos) as u32, // n-d-1= 1, 0
-                                                           &symtbl,&mut rib_heap));
-                    }
-                } else { // n < d
-                    if op<CNST { //CALL, SET, GET
-                        n_field = RibField::Rib(symbol_ref(n as u32,&symtbl,&mut rib_heap));
-                    } else { //CNST, IF, HALT
-                        n_field = RibField::Number(n);
+        /// Returns the name and value of every global variable of the loaded
+        /// program that has a name, see `get_global`.
+        pub fn globals(&mut self) -> Result<Vec<(String, RibField)>, VmError> {
+            let mut globals = Vec::new();
+            let mut pair = self.symtbl;
+            while pair != NIL_REF {
+                let sym = self.rib_heap.get(&pair).first.get_rib(&mut self.rib_heap)?;
+                let name = scm2str(&sym.middle, &mut self.rib_heap)?;
+                if !name.is_empty() {
+                    globals.push((name, sym.first));
+                }
+                pair = self.rib_heap.get(&pair).middle.get_rib_ref()?;
+            }
+            Ok(globals)
+        }
 
-                    }
-                };
-                if op>IF {
-                    let popped = pop_stack(&mut stack,&mut rib_heap);
-                    let inner = rib_heap.push_rib(make_rib(
-                        n_field,
-                        RibField::Number(0),
-                        popped
-                    ));
-                    n_field = RibField::Rib(rib_heap.push_rib(make_data_rib(
-                        RibField::Rib(inner),
-                        RibField::Rib(NIL_REF),
-                        PROCEDURE
-                    )));
-                    if !is_rib(&rib_heap.get(&stack).middle) {break};
-                    op = IF;
-                };
+        /// Calls the procedure in the global variable `name` of the loaded
+        /// program with `args`, and returns its result. The program has
+        /// usually run beforehand to define `name`; it may also be paused by
+        /// `run_for`, and is left as it was in any case. Errors are handled
+        /// as in `run`.
+        ///
+        /// The result is only valid until the next garbage collection, see
+        /// `RibHeap`.
+        pub fn call(&mut self, name: &str, args: &[RibField]) -> Result<RibField, VmError> {
+            let sym = match self.find_symbol(name)? {
+                Some(sym) => sym,
+                None => return Err(VmError::UnknownGlobal(String::from(name))),
             };
+            let procedure = self.rib_heap.get(&sym).first;
+            if !is_rib(&procedure) || procedure.get_rib(&mut self.rib_heap)?.last != RibField::number(PROCEDURE) {
+                return Err(VmError::TypeError(format!("{} is not a procedure", name)))
+            }
 
-            // Il ne fait que push des n0, ils sont modifiés ici
-            let stack_first= rib_heap.get(&stack).first;
-            let new_rib_ref = rib_heap.push_rib(
-                make_op_rib(
-                    op-1 as i32,
-                    n_field,
-                    stack_first
-                ));
-            let mut top_stack = rib_heap.get(&stack);
-            top_stack.first = RibField::Rib(new_rib_ref);
-            rib_heap.set(&stack, top_stack); // <- Là, spécifiquement
-        };
-
+            // The collector moves the registers of the interrupted program
+            let stack = self.stack.spill(&mut self.rib_heap);
+            let stack = self.rib_heap.root(RibField::rib(stack));
+            let pc = self.rib_heap.root(RibField::rib(self.pc));
+            let raised = self.raised.take();
 
-        let n_first = n_field.get_rib(&mut rib_heap).first;
-        let mut pc: RibField = n_first.get_rib(&mut rib_heap).last;
+            for arg in args {
+                self.stack.push(*arg);
+            }
+            // @@(feature arity-check
+            self.stack.push(RibField::number(args.len() as i64));
+            // )@@
 
+            // The callee returns to this halt, like the program does to the
+            // one of the primordial continuation. The VM halts elsewhere when
+            // the callee escapes from the call.
+            let halt_instr = self.rib_heap.push_rib(make_op_rib(HALT,
+                                                                RibField::number(0),
+                                                                RibField::number(0)));
+            let sentinel = self.rib_heap.root(RibField::rib(halt_instr));
+            self.pc = self.rib_heap.push_rib(make_op_rib(CALL,
+                                                         RibField::rib(sym),
+                                                         RibField::rib(halt_instr)));
+
+            let result = self.run();
+            let returned = self.rib_heap.unroot(sentinel)? == RibField::rib(self.pc);
+            let result = match result {
+                Ok(RunStatus::Exited(status)) => Err(VmError::Exited(status)),
+                Ok(_) if !returned => Err(VmError::CallEscaped),
+                Ok(_) => self.stack.pop(&mut self.rib_heap),
+                Err(err) => Err(err),
+            };
 
-        set_global(rib_heap.push_rib(make_data_rib(RibField::Number(0),
-                                                   RibField::Rib(symtbl),
-                                                   PROCEDURE)),
-                   &mut symtbl, &mut rib_heap);
-        set_global(FALSE_REF,
-                   &mut symtbl, &mut rib_heap);
-        set_global(TRUE_REF,
-                   &mut symtbl, &mut rib_heap);
-        set_global(NIL_REF,
-                   &mut symtbl, &mut rib_heap);
+            let stack = self.rib_heap.unroot(stack)?.get_rib_ref()?;
+            self.stack.reset(stack);
+            self.pc = self.rib_heap.unroot(pc)?.get_rib_ref()?;
+            self.raised = raised;
+            result
+        }
 
-        // Il faut assigner le symbole "list" à la primitive list, si elle est présente
+        /// Writes out the output the program produced so far. The VM buffers
+        /// it and only flushes it when the program reads input, exits or
+        /// halts, or calls `flush-output`.
+        pub fn flush(&mut self) -> Result<(), VmError> {
+            flush_output(&mut self.output)
+        }
 
+        /// Makes the garbage collector incremental. A major collection then
+        /// copies a few objects at each instruction instead of all of them at
+        /// once, which bounds the pauses of the program.
+        pub fn set_incremental_gc(&mut self, incremental: bool) {
+            self.incremental_gc = incremental;
+        }
 
+        /// Makes the interpreter decode the operations of the program once
+        /// rather than each time they are executed, which is the default.
+        /// Decoded operations take some memory besides the heap.
+        pub fn set_predecoding(&mut self, predecoding: bool) {
+            self.rib_heap.code.enabled = predecoding;
+            self.rib_heap.code.forget();
+        }
 
-        let halt_instr = rib_heap.push_rib(make_op_rib(HALT,
-                                                       RibField::Number(0),
-                                                       RibField::Number(0)));
+        /// Executes the loaded program with the native code `translate`
+        /// produced from it rather than by interpreting its operations, up
+        /// to the first write of one of them. It must be given after `load`
+        /// and before the program runs, as the generated `load` does;
+        /// `VmError::NativeCodeMismatch` is returned for the native code of
+        /// another program.
+        pub fn set_native_code(&mut self, blocks: &[NativeBlock]) -> Result<(), VmError> {
+            let found = native_blocks(&mut self.rib_heap, self.pc)?;
+            if found.len() != blocks.len() ||
+                found.iter().zip(blocks).any(|(&(_, len), block)| len != block.len) {
+                return Err(VmError::NativeCodeMismatch)
+            }
+            self.clear_native_code()?;
+            // The collector keeps the starts and operands in a list, which
+            // map_native_code reads again when it is moved
+            let operands = native_operands(&mut self.rib_heap, &found)?;
+            let mut roots = NIL_REF;
+            for &x in operands.iter().rev() {
+                push_stack(x, &mut roots, &mut self.rib_heap);
+            }
+            for &(start, _) in found.iter().rev() {
+                push_stack(RibField::rib(start), &mut roots, &mut self.rib_heap);
+            }
+            self.native_roots = Some((self.rib_heap.root(RibField::rib(roots)), NIL_REF));
+            self.native = blocks.to_vec();
+            self.map_native_code()
+        }
 
-        let primordial_cont = make_op_rib(CALL,
-                                          RibField::Number(0),
-                                          RibField::Rib(halt_instr));
+        fn clear_native_code(&mut self) -> Result<(), VmError> {
+            if let Some((roots, _)) = self.native_roots.take() {
+                self.rib_heap.unroot(roots)?;
+            }
+            self.native_starts.clear();
+            self.native_operands.clear();
+            self.native.clear();
+            self.rib_heap.code.native.clear();
+            self.rib_heap.code.native_written = false;
+            Ok(())
+        }
 
-        stack = rib_heap.push_rib(primordial_cont);
+        // Finds the native blocks and their operands in the heap, after a
+        // collection moved them
+        fn map_native_code(&mut self) -> Result<(), VmError> {
+            let (roots, mapped) = match &mut self.native_roots {
+                Some((roots, mapped)) => (self.rib_heap.rooted(roots)?.get_rib_ref()?, mapped),
+                None => return Ok(()),
+            };
+            // A collection that moves the list moves what it holds
+            if roots != *mapped {
+                *mapped = roots;
+                let mut values = roots;
+                self.native_starts.clear();
+                self.native_operands.clear();
+                while values != NIL_REF {
+                    let x = pop_stack(&mut values, &mut self.rib_heap)?;
+                    if self.native_starts.len() < self.native.len() {
+                        self.native_starts.push(x.get_rib_ref()?);
+                    } else {
+                        self.native_operands.push(x);
+                    }
+                }
+            }
+            let code = &self.rib_heap.code;
+            if code.native_written || !code.native.is_empty() {
+                return Ok(())
+            }
+            let mut native = vec![0; self.rib_heap.heap.len()];
+            for (b, (block, &start)) in self.native.iter().zip(&self.native_starts).enumerate() {
+                let mut pc = start;
+                native[pc] = b as u32 + 1;
+                for _ in 1..block.len {
+                    pc = self.rib_heap[pc].last.get_rib_ref()?;
+                    native[pc] = IN_BLOCK;
+                }
+            }
+            self.rib_heap.code.native = native;
+            Ok(())
+        }
 
+        /// Limits the heap to `max_size` ribs, or lets it grow without limit
+        /// with `None`, the default. When a collection leaves too little
+        /// room to allocate, `VmError::HeapExhausted` is raised in the
+        /// program. Without a handler, `run` returns it and the program can
+        /// be resumed once it has more room, for example with a higher limit.
+        /// A primitive call that allocates past the limit raises the error
+        /// too, in place of its result.
+        pub fn set_max_heap_size(&mut self, max_size: Option<usize>) {
+            self.rib_heap.max_size = max_size.unwrap_or(usize::MAX);
+        }
 
-        // @@(feature debug
-        if tracing {
-            eprintln!("{}",show(&pc,&mut rib_heap));
+        /// Sets how much the heap may grow between two major collections,
+        /// in percent of the live objects found by the last one. The default
+        /// of 100 lets it double; a lower value uses less memory, but
+        /// collects more often.
+        pub fn set_heap_growth(&mut self, percent: usize) {
+            self.heap_growth = percent;
         }
-        // )@@
 
-        // let mut pc_trace = show(&pc, &mut rib_heap);
-        // let mut stack_trace = show_stack(&stack, &mut rib_heap);
-        // let mut getchar_calls = 0;
+        /// Makes `getchar` read from `input` instead of standard input,
+        /// for example an in-memory `&[u8]` or `Cursor<String>`.
+        pub fn set_input<R: Read + 'static>(&mut self, input: R) {
+            self.input = Box::new(BufReader::new(input));
+        }
 
-        let mut size_of_heap =rib_heap.heap.len();
-        if heap_tracing {
-            eprintln!("Heap size before first gc: {}", size_of_heap);
+        /// Makes `putchar` write to `output` instead of standard output.
+        /// What the program wrote so far is flushed to the previous output.
+        pub fn set_output<W: Write + 'static>(&mut self, output: W) -> Result<(), VmError> {
+            self.flush()?;
+            *self.output.get_mut() = Output::Writer(Box::new(output));
+            Ok(())
         }
 
-        let mut pc_ref = pc.get_rib_ref();
-        size_of_heap = rib_heap.garbage_collect(&mut stack, &mut pc_ref, &mut symtbl);
-        pc = RibField::Rib(pc_ref);
+        /// Makes `putchar` write to a buffer in memory, which `take_output`
+        /// retrieves. What the program wrote so far is flushed to the
+        /// previous output.
+        pub fn capture_output(&mut self) -> Result<(), VmError> {
+            self.flush()?;
+            *self.output.get_mut() = Output::Captured(Vec::new());
+            Ok(())
+        }
 
-        if heap_tracing {
-            eprintln!("Heap size after first gc: {}", size_of_heap);
+        /// Returns the output captured since the last call, or since
+        /// `capture_output`. Invalid UTF-8 is replaced by U+FFFD, and the
+        /// result is empty when the output is not being captured.
+        pub fn take_output(&mut self) -> Result<String, VmError> {
+            self.flush()?;
+            match self.output.get_mut() {
+                Output::Captured(captured) =>
+                    Ok(String::from_utf8_lossy(&std::mem::take(captured)).into_owned()),
+                Output::Writer(_) => Ok(String::new()),
+            }
         }
 
-        let mut gc_count: u32 = 1;
+        /// Calls the exception handler with an error object holding the
+        /// message of `err` and no irritants, in place of the instruction
+        /// that failed. The handler is not expected to return, and the VM
+        /// stops with `err` if it does. Without a handler `err` is returned
+        /// as is, and a handler that does not take one argument, or more
+        /// with a rest parameter, is rejected.
+        fn raise(&mut self, err: VmError) -> Result<(), VmError> {
+            let handler = RibField::rib(self.handler);
+            let handler_rib = handler.get_rib(&mut self.rib_heap)?;
+            if handler_rib.last != RibField::number(PROCEDURE) {
+                return Err(err)
+            }
+            let code = handler_rib.first;
+            let nparams = match handler_nparams(handler, &mut self.rib_heap)? {
+                Some(nparams) => nparams,
+                None => return Err(VmError::RejectedHandler(Box::new(err))),
+            };
+            let variadic = nparams % 2 == 1;
+
+            let message = str2scm(&err.to_string(), &mut self.rib_heap);
+            let obj = RibField::rib(self.rib_heap.push_rib(make_rib(message,
+                                                                    RibField::rib(NIL_REF),
+                                                                    RibField::number(ERROR_OBJECT))));
+            let halt_instr = self.rib_heap.push_rib(make_op_rib(HALT,
+                                                                RibField::number(1),
+                                                                RibField::number(0)));
+            let stack = self.stack.spill(&mut self.rib_heap);
+            let s2 = self.rib_heap.push_rib(make_rib(RibField::rib(stack),
+                                                     handler,
+                                                     RibField::rib(halt_instr)));
+            self.stack.reset(s2);
+            // The frame is the one jump_or_call makes for a call with the
+            // error object as only argument
+            match nparams >> 1 {
+                _ if !variadic => self.stack.push(obj),
+                0 => {
+                    let rest = self.rib_heap.push_rib(make_rib(obj, RibField::rib(NIL_REF), RibField::number(PAIR)));
+                    self.stack.push(RibField::rib(rest));
+                },
+                _ => {
+                    self.stack.push(RibField::rib(NIL_REF));
+                    self.stack.push(obj);
+                },
+            }
+            self.pc = code.get_rib(&mut self.rib_heap)?.last.get_rib_ref()?;
+            self.raised = Some(err);
+            Ok(())
+        }
 
-        loop{
+        /// Executes the instruction at the program counter and tells
+        /// whether it halted the VM.
+        fn step(&mut self) -> Result<bool, VmError> {
             // @@(feature debug
-            if debug {
-                start_step(&mut step_count, &mut tracing, &mut next_stamp, &start_tracing , &stack, &mut rib_heap);
+            if self.debug {
+                let stack = self.stack.spill(&mut self.rib_heap);
+                start_step(&mut self.step_count, &mut self.tracing, &mut self.next_stamp, &self.start_tracing , &stack, &mut self.rib_heap)?;
             }
             // )@@
-            let mut o = pc.get_rib(&mut rib_heap).middle;
-            let pc_instr = pc.get_rib(&mut rib_heap).first.get_number();
-            match pc_instr {
-                HALT => {
-                    if tracing {eprintln!("halt");} // @@(feature debug)@@
-                    return},
+            match self.rib_heap.instr(self.pc)? {
+                Instr::Halt(o) => {
+                    if self.tracing {eprintln!("halt");} // @@(feature debug)@@
+                    return self.halt(o)},
                 // jump/call
-                CALL => {
+                Instr::Call { opnd: o, next } => {
                     // @@(feature debug
-                    if tracing {
-                        if is_rib(&pc.get_rib(&mut rib_heap).last) {
-                            eprintln!("call {}",show(&o,&mut rib_heap));
+                    if self.tracing {
+                        if is_rib(&next) {
+                            eprintln!("call {}",show(&o,&mut self.rib_heap)?);
                         } else {
-                            eprintln!("jump {}",show(&o,&mut rib_heap));
+                            eprintln!("jump {}",show(&o,&mut self.rib_heap)?);
                         }
                     }
                     // )@@
-                    // @@(feature arity-check
-                    let pre_o =o;
-                    let mut nargs = -1;
-                    if is_rib(&pre_o) {
-                        nargs = pop_stack(&mut stack, &mut rib_heap).get_number();
+                    self.jump_or_call(o, next)?;
+                },
+                Instr::Set { opnd: o, next } => {
+                    if self.tracing {eprintln!("set {}",show(&o, &mut self.rib_heap)?);}  // @@(feature debug)@@
+                    self.stack.set(&o,&mut self.rib_heap)?;
+                    self.pc = next;
+                },
+                Instr::Get { opnd: o, next } => {
+                    if self.tracing {eprintln!("get {}",show(&o, &mut self.rib_heap)?);} // @@(feature debug)@@
+                    let gotten_element = self.stack.get(&o,&mut self.rib_heap)?;
+                    self.stack.push(gotten_element);
+                    self.pc = next;
+                },
+                Instr::Const { value: o, next } => {
+                    if self.tracing {eprintln!("const {}",show(&o, &mut self.rib_heap)?);} //@@(feature debug)@@
+                    self.stack.push(o);
+                    self.pc = next;
+                },
+                Instr::If { then, next } => {
+                    if self.tracing {eprintln!("if"); }                                  //@@(feature debug)@@
+                    self.branch(then, next)?;
+                },
+            }
+            Ok(false)
+        }
+
+        // The halt operation, whose operand is 1 for the halt pushed by raise.
+        // When the exception handler returned to that halt, the error stays
+        // in raised for run_for to return it, and not raise it again.
+        fn halt(&mut self, o: RibField) -> Result<bool, VmError> {
+            if o != RibField::number(1) {
+                self.raised = None; // the handler did not return
+            }
+            Ok(true)
+        }
+
+        fn branch(&mut self, then: usize, next: usize) -> Result<(), VmError> {
+            let bool_expr = self.stack.pop(&mut self.rib_heap)?;
+            if is_rib(&bool_expr) && bool_expr.get_rib_ref()? == FALSE_REF
+            {
+                self.pc = next;
+            } else {
+                self.pc = then;
+            };
+            Ok(())
+        }
+
+        // The call operation, a jump when next is 0
+        fn jump_or_call(&mut self, mut o: RibField, next: RibField) -> Result<(), VmError> {
+            // @@(feature arity-check
+            let pre_o =o;
+            let mut nargs = -1;
+            if is_rib(&pre_o) {
+                nargs = self.stack.pop(&mut self.rib_heap)?.get_number()?;
+            }
+                //)@@
+            o = self.stack.get(&o, &mut self.rib_heap)?;
+            let mut c = o.get_rib(&mut self.rib_heap)?.first;
+
+            // @@(feature arity-check
+            if !is_rib(&pre_o) {
+                nargs = self.stack.pop(&mut self.rib_heap)?.get_number()?;
+            }
+            //)@@
+
+            if is_rib(&c){ // c: code
+                let mut nparams = c.get_rib(&mut self.rib_heap)?
+                    .first.get_number()?;
+
+                // @@(feature arity-check
+                let variadic = nparams % 2==1;
+                // )@@
+
+                nparams >>= 1;
+
+                // @@(feature arity-check
+                if !variadic && nparams != nargs || variadic && nparams > nargs
+                {
+                    return Err(incoherent_nargs_stop(nargs as u32, nparams as u32, variadic));
+                }
+                // )@@
+
+                // @@(feature rest-param (use arity-check)
+                nargs -= nparams;
+                let mut rest = NIL_REF;
+                if variadic
+                {
+                    let mut i =0;
+                    while i < nargs {
+                        let arg = self.stack.pop(&mut self.rib_heap)?;
+                        push_stack(arg, &mut rest, &mut self.rib_heap);
+                        i += 1;
                     }
-                        //)@@
-                    let opnd_ref =get_opnd(&o, &stack, &mut rib_heap);
-                    o = opnd_ref.first;
-                    let mut c = o.get_rib(&mut rib_heap).first;
+                }
+                // )@@
 
-                    // @@(feature arity-check
-                    if !is_rib(&pre_o) {
-                        nargs = pop_stack(&mut stack, &mut rib_heap).get_number();
+                let mut args = std::mem::take(&mut self.stack.args);
+                while nparams >0{
+                    args.push(self.stack.pop(&mut self.rib_heap)?);
+                    nparams -=1;
+                };
+                let mut c2 = make_rib(RibField::number(0),
+                                      RibField::rib(o.get_rib_ref()?),
+                                      RibField::number(PAIR));
+                if is_rib(&next) {
+                    //It's a call
+                    c2.first=RibField::rib(self.stack.spill(&mut self.rib_heap));
+                    c2.last=next;
+                } else {
+                    //It's a jump
+                    let k = self.stack.cont(&mut self.rib_heap)?;
+                    c2.first=self.rib_heap.get(&k).first;
+                    c2.last=self.rib_heap.get(&k).last;
+                };
+
+                // The frame starts the stack of the callee, with
+                // the arguments on top
+                let s2 = self.rib_heap.push_rib(c2);
+                self.stack.reset(s2);
+                // @@(feature rest-param (use arity-check)
+                if variadic
+                {
+                    self.stack.push(RibField::rib(rest));
+                }
+                // )@@
+                for arg in args.drain(..) {
+                    self.stack.push(arg);
+                }
+                self.stack.args = args;
+                self.pc = c.get_rib(&mut self.rib_heap)?.last.get_rib_ref()?;
+
+            } else {
+                self.rib_heap.exhausted = false;
+                primitives(c.get_number()?,
+                           // @@(feature arity-check
+                           nargs as u32,
+                           // )@@
+                           self)?;
+                // The collections keep the heap below its maximum size
+                // between operations, but a primitive may allocate past it
+                if self.rib_heap.exhausted {
+                    self.stack.pop(&mut self.rib_heap)?;
+                    return Err(VmError::HeapExhausted)
+                }
+                if next != RibField::number(0) {
+                    //It's a call
+                    self.pc = next.get_rib_ref()?;
+                } else {
+                    //It's a jump
+                    let result = self.stack.pop(&mut self.rib_heap)?;
+                    c= RibField::rib(self.stack.cont(&mut self.rib_heap)?);
+                    let saved = c.get_rib(&mut self.rib_heap)?.first;
+                    match saved.as_rib() {
+                        Some(s) => {
+                            self.stack.reset(s);
+                            self.stack.push(result);
+                        },
+                        // The primordial continuation saves no stack
+                        None => {
+                            let s = self.rib_heap.push_rib(make_data_rib(result, saved, PAIR));
+                            self.stack.reset(s);
+                        },
                     }
-                    //)@@
+                    self.pc = c.get_rib(&mut self.rib_heap)?.last.get_rib_ref()?;
+                }
+            }
+            Ok(())
+        }
+    }
 
-                    if is_rib(&c){ // c: code
-                        let mut nparams = c.get_rib(&mut rib_heap)
-                            .first.get_number();
+    // Native code
+
+    /// A block of operations of a program translated to Rust by
+    /// `translate`. A block starts at the entry of a procedure, after a
+    /// call or at a branch of an `if`, and runs up to the next call, `if`
+    /// or `halt`, or to the start of another block.
+    #[derive(Clone,Copy)]
+    pub struct NativeBlock {
+        /// Executes the operations of the block and tells whether the
+        /// program halted.
+        pub run: BlockFn,
+        /// The number of operations of the block.
+        pub len: u32,
+    }
 
-                        // @@(feature arity-check
-                        let variadic = nparams % 2==1;
-                        // )@@
+    /// The VM as seen by the native code of a program. Its methods execute
+    /// the operations of a block with the operands `translate` resolved:
+    /// slots and fixnums as is, and the other objects by their index among
+    /// the operands of the program, which the VM keeps rooted as the
+    /// collector moves them. The blocks that follow are given by index,
+    /// along with the function that runs them.
+    pub struct Native<'a> {
+        vm: &'a mut Vm,
+        fuel: u64,                         // for the blocks that follow
+        block: usize,                      // the block running
+        next: Option<(usize, BlockFn)>,    // the block that follows it
+    }
 
-                        nparams = nparams >>1;
+    impl<'a> Native<'a> {
+        // Runs the block of the VM at its pc, then the blocks that follow it
+        // one after the other, so that native code uses a bounded Rust stack
+        fn run(vm: &'a mut Vm, fuel: u64, block: usize) -> (Result<bool, VmError>, u64) {
+            let run = vm.native[block].run;
+            let mut native = Native { vm, fuel, block, next: None };
+            let mut result = run(&mut native);
+            while let (Ok(false), Some((block, run))) = (&result, native.next.take()) {
+                native.block = block;
+                result = run(&mut native);
+            }
+            (result, native.fuel)
+        }
 
-                        // @@(feature arity-check
-                        if !variadic && nparams != nargs || variadic && nparams > nargs
-                        {
-                            incoherent_nargs_stop(nargs as u32, nparams as u32, variadic);
-                        }
-                        // )@@
+        // The pc of the operation op of the block
+        fn op_pc(&self, block: usize, op: u32) -> Result<usize, VmError> {
+            let mut pc = self.block_start(block);
+            for _ in 0..op {
+                pc = self.vm.rib_heap[pc].last.get_rib_ref()?;
+            }
+            Ok(pc)
+        }
 
-                        let mut c2 = make_rib(RibField::Number(0),
-                                              RibField::Rib(o.get_rib_ref()),
-                                              RibField::Number(PAIR));
-                        let mut s2 = rib_heap.push_rib(c2);
-                        let c2_ref = s2;
+        #[inline]
+        fn operand(&self, operand: usize) -> RibField {
+            self.vm.native_operands[operand]
+        }
 
-                        // @@(feature rest-param (use arity-check)
-                        nargs -= nparams;
-                        if variadic
-                        {
-                            let mut rest = NIL_REF;
-                            let mut i =0;
-                            while i < nargs {
-                                let arg =pop_stack(&mut stack, &mut rib_heap);
-                                push_stack(arg, &mut rest, &mut rib_heap);
-                                i -= 1;
-                            }
-                            push_stack(RibField::Rib(rest), &mut s2, &mut rib_heap);
-                        }
-                        // )@@
+        #[inline]
+        fn block_start(&self, block: usize) -> usize {
+            self.vm.native_starts[block]
+        }
 
-                        while nparams >0{
-                            let popped =pop_stack(&mut stack,&mut rib_heap);
-                            push_stack(popped,&mut s2,&mut rib_heap);
-                            nparams -=1;
-                        };
-                        if is_rib(&pc.get_rib(&mut rib_heap).last) {
-                            //It's a call
-                            c2.first=RibField::Rib(stack);
-                            c2.last=pc.get_rib(&mut rib_heap).last;
-                            rib_heap.set(&c2_ref,c2);
-                        } else {
-                            //It's a jump
-                            let k = get_cont(&stack, &mut rib_heap);
-                            c2.first=rib_heap.get(&k).first;
-                            c2.last=rib_heap.get(&k).last;
-                            rib_heap.set(&c2_ref,c2);
-                        };
+        /// A `get` of a slot of the stack.
+        #[inline]
+        pub fn get_slot(&mut self, slot: u32) -> Result<(), VmError> {
+            let x = self.vm.stack.get(&RibField::number(slot as i64), &mut self.vm.rib_heap)?;
+            self.vm.stack.push(x);
+            Ok(())
+        }
 
-                        stack = s2;
+        /// A `get` of a global variable.
+        #[inline]
+        pub fn get_global(&mut self, operand: usize) -> Result<(), VmError> {
+            let x = self.vm.stack.get(&self.operand(operand), &mut self.vm.rib_heap)?;
+            self.vm.stack.push(x);
+            Ok(())
+        }
 
-                    } else {
-                        primitives(c.get_number() as u8,
-                                   // @@(feature arity-check
-                                   nargs as u32,
-                                   // )@@
-                                   &mut stack, &mut rib_heap);
-                        if is_rib(&pc.get_rib(&mut rib_heap).last)
-                            || pc.get_rib(&mut rib_heap).last.get_number() !=0 {
-                            //It's a call
-                            c = pc;
-                        } else {
-                            //It's a jump
-                            c= RibField::Rib(get_cont(&stack, &mut rib_heap));
-                            let mut top_stack = rib_heap.get(&stack);
-                            top_stack.middle = c.get_rib(&mut rib_heap).first;
-                            rib_heap.set(&stack,top_stack);
-                        }
-                    }
-                    pc = c.get_rib(&mut rib_heap).last;
-                },
-                SET => {
-                    if tracing {eprintln!("set {}",show(&o, &mut rib_heap));}  // @@(feature debug)@@
-                    let set_rib_index = get_opnd_ref(&o,&stack,&mut rib_heap);
-                    let mut set_rib = rib_heap.get(&set_rib_index);
-                    let top =pop_stack(&mut stack,&mut rib_heap);
-                    set_rib.first = top;
-                    rib_heap.set(&set_rib_index,set_rib);
-                    pc = pc.get_rib(&mut rib_heap).last;
-                },
-                GET => {
-                    if tracing {eprintln!("get {}",show(&o, &mut rib_heap));} // @@(feature debug)@@
-                    let opnd_ref =get_opnd(&o,&stack,&mut rib_heap);
-                    let gotten_element =
-                        opnd_ref.first;
-                    push_stack(gotten_element,&mut stack, &mut rib_heap);
-                    pc = pc.get_rib(&mut rib_heap).last;
-                },
-                CNST => {
-                    if tracing {eprintln!("const {}",show(&o, &mut rib_heap));} //@@(feature debug)@@
-                    push_stack(o,&mut stack,&mut rib_heap);
-                    pc = pc.get_rib(&mut rib_heap).last;
-                },
-                IF => {
+        /// A `set` of a slot of the stack.
+        #[inline]
+        pub fn set_slot(&mut self, slot: u32) -> Result<(), VmError> {
+            self.vm.stack.set(&RibField::number(slot as i64), &mut self.vm.rib_heap)
+        }
 
-                    let bool_expr = pop_stack(&mut stack, &mut rib_heap);
-                    if tracing {eprintln!("if"); }                                  //@@(feature debug)@@
-                    if is_rib(&bool_expr) && bool_expr.get_rib_ref() == FALSE_REF
-                    {
-                        pc = pc.get_rib(&mut rib_heap).last;
-                    } else {
-                        pc = pc.get_rib(&mut rib_heap).middle;
-                    };
-                },
-                _ => panic!("Unimplemented instruction number {}",pc_instr),
-            };
+        /// A `set` of a global variable.
+        #[inline]
+        pub fn set_global(&mut self, operand: usize) -> Result<(), VmError> {
+            self.vm.stack.set(&self.operand(operand), &mut self.vm.rib_heap)
+        }
+
+        /// A `const` of a fixnum.
+        #[inline]
+        pub fn push_number(&mut self, n: i64) {
+            self.vm.stack.push(RibField::number(n));
+        }
+
+        /// A `const` of an object.
+        #[inline]
+        pub fn push_object(&mut self, operand: usize) {
+            let x = self.operand(operand);
+            self.vm.stack.push(x);
+        }
+
+        /// A call of the procedure in a slot, the end of a block. The program
+        /// goes on with the block `next` when the procedure is a primitive,
+        /// and otherwise at the entry of the procedure, which the VM
+        /// dispatches.
+        #[inline]
+        pub fn call_slot(&mut self, slot: u32, next: usize, run: BlockFn) -> Result<bool, VmError> {
+            self.call(RibField::number(slot as i64), next, run)
+        }
+
+        /// A call of the procedure in a global variable, like `call_slot`.
+        #[inline]
+        pub fn call_global(&mut self, operand: usize, next: usize, run: BlockFn) -> Result<bool, VmError> {
+            self.call(self.operand(operand), next, run)
+        }
+
+        #[inline]
+        fn call(&mut self, o: RibField, next: usize, run: BlockFn) -> Result<bool, VmError> {
+            let next_pc = self.block_start(next);
+            if let Err(err) = self.vm.jump_or_call(o, RibField::rib(next_pc)) {
+                return Err(self.fail_last(err))
+            }
+            if self.vm.pc == next_pc {
+                self.goto(next, run)
+            } else {
+                Ok(false)
+            }
+        }
+
+        /// A jump to the procedure in a slot, the end of a block.
+        #[inline]
+        pub fn jump_slot(&mut self, slot: u32) -> Result<bool, VmError> {
+            match self.vm.jump_or_call(RibField::number(slot as i64), RibField::number(0)) {
+                Ok(()) => Ok(false),
+                Err(err) => Err(self.fail_last(err)),
+            }
+        }
+
+        /// A jump to the procedure in a global variable.
+        #[inline]
+        pub fn jump_global(&mut self, operand: usize) -> Result<bool, VmError> {
+            match self.vm.jump_or_call(self.operand(operand), RibField::number(0)) {
+                Ok(()) => Ok(false),
+                Err(err) => Err(self.fail_last(err)),
+            }
+        }
+
+        /// The test of an `if`, which pops the value tested.
+        #[inline]
+        pub fn test(&mut self) -> Result<bool, VmError> {
+            let x = self.vm.stack.pop(&mut self.vm.rib_heap)?;
+            Ok(x != RibField::rib(FALSE_REF))
+        }
+
+        /// Goes on with the block `block`, which `run` executes once the
+        /// running block returned. The VM dispatches it instead when the
+        /// fuel runs out, the code of the program was written or the heap
+        /// needs a collection.
+        #[inline]
+        pub fn goto(&mut self, block: usize, run: BlockFn) -> Result<bool, VmError> {
+            let len = u64::from(self.vm.native[block].len);
+            let heap = &self.vm.rib_heap;
+            if len <= self.fuel && !heap.code.native_written && heap.collection.is_none() &&
+                heap.nursery_len() <= NURSERY_SIZE.min(heap.max_size / 4)
+            {
+                self.fuel -= len;
+                self.next = Some((block, run));
+                Ok(false)
+            } else {
+                self.vm.pc = self.block_start(block);
+                Ok(false)
+            }
+        }
+
+        /// The `halt` that ends the block `block`.
+        #[inline]
+        pub fn halt(&mut self, block: usize) -> Result<bool, VmError> {
+            let pc = self.op_pc(block, self.vm.native[block].len - 1)?;
+            self.vm.pc = pc;
+            self.vm.halt(self.vm.rib_heap[pc].middle)
+        }
+
+        /// Stops the running block at its operation `op`, which failed with
+        /// `err`: the program is left at that operation, as when it is
+        /// interpreted, and `err` is returned.
+        #[cold]
+        pub fn fail(&mut self, op: u32, err: VmError) -> VmError {
+            if let Ok(pc) = self.op_pc(self.block, op) {
+                self.vm.pc = pc;
+            }
+            err
+        }
+
+        // Stops the running block at its last operation, a call or a jump
+        #[cold]
+        fn fail_last(&mut self, err: VmError) -> VmError {
+            self.fail(self.vm.native[self.block].len - 1, err)
+        }
+    }
 
+    /// The function of a `NativeBlock`.
+    pub type BlockFn = fn(&mut Native) -> Result<bool, VmError>;
 
-            if 2*size_of_heap < rib_heap.heap.len() {
-                gc_count += 1;
+    // The code of the procedure o, if it is a closure or a lambda
+    fn procedure_entry(o: &RibField, holder: &mut RibHeap) -> Option<usize> {
+        let procedure = holder.get(&o.as_rib()?);
+        if procedure.last != RibField::number(PROCEDURE) {
+            return None
+        }
+        holder.get(&procedure.first.as_rib()?).last.as_rib()
+    }
 
-                // @@(feature debug
-                if heap_tracing {
-                    size_of_heap = rib_heap.heap.len();
-                    eprintln!("Heap size before {}th gc: {}", gc_count, size_of_heap);
+    // The blocks of the program that starts at pc, as the index of their
+    // first operation and their number of operations. Their order only
+    // depends on the code, so that the program finds its blocks again after
+    // being loaded anew.
+    fn native_blocks(holder: &mut RibHeap, pc: usize) -> Result<Vec<(usize, u32)>, VmError> {
+        let mut starts = vec![pc];
+        let mut is_start = vec![false; holder.heap.len()];
+        is_start[pc] = true;
+        let mut i = 0;
+        while i < starts.len() {
+            let mut pc = starts[i];
+            let mut targets = Vec::new();
+            let mut len = 0;
+            loop {
+                match Instr::decode(&holder.get(&pc))? {
+                    Instr::Get { next, .. } | Instr::Set { next, .. } => pc = next,
+                    Instr::Const { value, next } => {
+                        if let Some(entry) = procedure_entry(&value, holder) {
+                            targets.push(entry);
+                        }
+                        pc = next;
+                    },
+                    Instr::Call { next, .. } => {
+                        if let Some(next) = next.as_rib() {
+                            targets.push(next);
+                        }
+                        break
+                    },
+                    Instr::If { then, next } => {
+                        targets.push(then);
+                        targets.push(next);
+                        break
+                    },
+                    Instr::Halt(_) => break,
                 }
-                // )@@
-                pc_ref = pc.get_rib_ref();
-                size_of_heap = rib_heap.garbage_collect(&mut stack,&mut pc_ref, &mut symtbl);
-                pc = RibField::Rib(pc_ref);
-                // @@(feature debug
-                if heap_tracing {
-                    eprintln!("Heap size after {}th gc: {}", gc_count, size_of_heap);
+                len += 1;
+                if is_start[pc] {
+                    break
+                }
+                // Operations that loop without a call start a block
+                if len > holder.heap.len() {
+                    targets.push(pc);
+                    break
+                }
+            }
+            for target in targets {
+                if !is_start[target] {
+                    is_start[target] = true;
+                    starts.push(target);
+                }
+            }
+            i += 1;
+        }
+
+        let mut blocks = Vec::with_capacity(starts.len());
+        for start in starts {
+            let mut pc = start;
+            let mut len = 1;
+            loop {
+                match Instr::decode(&holder.get(&pc))? {
+                    Instr::Get { next, .. } | Instr::Set { next, .. } | Instr::Const { next, .. }
+                        if !is_start[next] => pc = next,
+                    _ => break,
+                }
+                len += 1;
+            }
+            blocks.push((start, len));
+        }
+        Ok(blocks)
+    }
+
+    // The operands of the native blocks that are ribs, numbered in the
+    // order of the blocks and of their operations
+    fn native_operands(holder: &mut RibHeap, blocks: &[(usize, u32)]) -> Result<Vec<RibField>, VmError> {
+        let mut operands = Vec::new();
+        for &(start, len) in blocks {
+            let mut pc = start;
+            for i in 0..len {
+                let rib = holder.get(&pc);
+                match Instr::decode(&rib)? {
+                    Instr::Get { opnd, .. } | Instr::Set { opnd, .. } | Instr::Call { opnd, .. } |
+                    Instr::Const { value: opnd, .. } if is_rib(&opnd) => operands.push(opnd),
+                    _ => (),
+                }
+                if i + 1 < len {
+                    pc = rib.last.get_rib_ref()?;
+                }
+            }
+        }
+        Ok(operands)
+    }
+
+    // The name of the global variable o, for the comments of translate
+    fn global_name(o: &RibField, holder: &mut RibHeap) -> Option<String> {
+        let sym = o.as_rib()?;
+        if holder.get(&sym).last != RibField::number(SYMBOL) {
+            return None
+        }
+        let name = holder.get(&sym).middle;
+        match scm2str(&name, holder) {
+            Ok(name) if !name.is_empty() => Some(name.escape_debug().to_string()),
+            _ => None,
+        }
+    }
+
+    /// Translates compacted RVM code to the source of a Rust module that
+    /// runs the program with native code, see `Vm::set_native_code`. Each
+    /// block of operations becomes a function that executes them one after
+    /// the other, without the decoding and dispatching of the interpreter,
+    /// then has the function of the block that follows run when it is known:
+    /// after a branch of an `if`, a call to a primitive or an operation
+    /// that starts another block. The entries of closures and the returns
+    /// of calls are dispatched by the VM. The frames, and so the
+    /// continuations, are the same ribs as when the program is interpreted.
+    ///
+    /// The module uses this crate as `ribbit` and has a `load` function
+    /// that loads the program and its native code in a `Vm`, in place of
+    /// `Vm::load`.
+    pub fn translate(rvm_code: &str) -> Result<String, VmError> {
+        let mut vm = Vm::new();
+        vm.load(rvm_code)?;
+        let holder = &mut vm.rib_heap;
+        let blocks = native_blocks(holder, vm.pc)?;
+        let mut block_at = vec![0; holder.heap.len()];
+        for (b, &(start, _)) in blocks.iter().enumerate() {
+            block_at[start] = b;
+        }
+
+        let mut module = String::new();
+        module.push_str("// Generated by `rvm --emit-rust`, see `ribbit::rvm::translate`.\n\n");
+        module.push_str("use ribbit::rvm::{Native, NativeBlock, Vm, VmError};\n\n");
+        module.push_str("/// Loads the program in `vm` with its native code.\n");
+        module.push_str("pub fn load(vm: &mut Vm) -> Result<(), VmError> {\n");
+        module.push_str("    vm.load(RVM_CODE)?;\n");
+        module.push_str("    vm.set_native_code(&BLOCKS)\n");
+        module.push_str("}\n\n");
+        module.push_str(&format!("pub const RVM_CODE: &str = {:?};\n\n", rvm_code));
+        module.push_str(&format!("static BLOCKS: [NativeBlock; {}] = [\n", blocks.len()));
+        for (b, &(_, len)) in blocks.iter().enumerate() {
+            module.push_str(&format!("    NativeBlock {{ run: block_{}, len: {} }},\n", b, len));
+        }
+        module.push_str("];\n");
+
+        let mut operand = 0;
+        for (b, &(start, len)) in blocks.iter().enumerate() {
+            module.push_str(&format!("\nfn block_{}(n: &mut Native) -> Result<bool, VmError> {{\n", b));
+            let mut pc = start;
+            for i in 0..len {
+                let rib = holder.get(&pc);
+                let instr = Instr::decode(&rib)?;
+                // The rib operand of the operation, if any, and the
+                // comment that names it
+                let (opnd, name) = match instr {
+                    Instr::Get { opnd, .. } | Instr::Set { opnd, .. } | Instr::Call { opnd, .. } |
+                    Instr::Const { value: opnd, .. } if is_rib(&opnd) => {
+                        operand += 1;
+                        let name = match global_name(&opnd, holder) {
+                            Some(name) => format!(" // {}", name),
+                            None => String::new(),
+                        };
+                        (operand - 1, name)
+                    },
+                    _ => (0, String::new()),
+                };
+                let line = match instr {
+                    Instr::Get { opnd: o, .. } => match o.as_number() {
+                        Some(slot) => format!("n.get_slot({}).map_err(|e| n.fail({}, e))?;", slot, i),
+                        None => format!("n.get_global({}).map_err(|e| n.fail({}, e))?;{}", opnd, i, name),
+                    },
+                    Instr::Set { opnd: o, .. } => match o.as_number() {
+                        Some(slot) => format!("n.set_slot({}).map_err(|e| n.fail({}, e))?;", slot, i),
+                        None => format!("n.set_global({}).map_err(|e| n.fail({}, e))?;{}", opnd, i, name),
+                    },
+                    Instr::Const { value, .. } => match value.as_number() {
+                        Some(x) => format!("n.push_number({});", x),
+                        None => format!("n.push_object({});{}", opnd, name),
+                    },
+                    Instr::Call { opnd: o, next } => match (o.as_number(), next.as_rib()) {
+                        (Some(slot), Some(next)) =>
+                            format!("n.call_slot({}, {1}, block_{1})", slot, block_at[next]),
+                        (None, Some(next)) =>
+                            format!("n.call_global({}, {1}, block_{1}){2}", opnd, block_at[next], name),
+                        (Some(slot), None) => format!("n.jump_slot({})", slot),
+                        (None, None) => format!("n.jump_global({}){}", opnd, name),
+                    },
+                    Instr::If { then, next } =>
+                        format!("if n.test().map_err(|e| n.fail({2}, e))? {{ n.goto({0}, block_{0}) }} \
+                                 else {{ n.goto({1}, block_{1}) }}",
+                                block_at[then], block_at[next], i),
+                    Instr::Halt(_) => format!("n.halt({})", b),
+                };
+                module.push_str("    ");
+                module.push_str(&line);
+                module.push('\n');
+                if i + 1 == len {
+                    // The block falls into the next one
+                    if let Instr::Get { next, .. } | Instr::Set { next, .. } | Instr::Const { next, .. } = instr {
+                        module.push_str(&format!("    n.goto({0}, block_{0})\n", block_at[next]));
+                    }
+                } else {
+                    pc = rib.last.get_rib_ref()?;
                 }
-                // )@@
             }
+            module.push_str("}\n");
         }
+        Ok(module)
+    }
+
+    /// Runs the program of the host generated by rsc and returns the exit
+    /// status of the process.
+    pub fn run_rvm() -> i32 {
+
+        // @@(replace ");'lvD?m>lvRD?m>lvRA?m>lvRA?m>lvR:?m>lvR=!(:nlkm!':nlkv6{" (encode 92)
+        let rvm_code: String = ");'lvD?m>lvRD?m>lvRA?m>lvRA?m>lvR:?m>lvR=!(:nlkm!':nlkv6{".to_string();
+        // )@@
+
+        let mut vm = Vm::new();
+        // @@(feature incremental-gc
+        vm.set_incremental_gc(true);
+        // )@@
+        match vm.load(&rvm_code).and_then(|()| vm.run()) {
+            Ok(RunStatus::Exited(status)) => status,
+            Ok(_) => 0,
+            Err(err) => {
+                eprintln!("{}", err);
+                1
+            },
+        }
+    }
+
+    /// Reads compacted RVM code from the file at `path`. When `path` is `-`
+    /// the code is the first line of standard input, and the rest of the
+    /// input stays available to the program through `getchar`. The code is
+    /// expected to be compiled by `rsc -t rvm -f+ arity-check`, see
+    /// `Vm::load`.
+    pub fn read_rvm_code(path: &str) -> std::io::Result<String> {
+        let mut rvm_code = String::new();
+        if path == "-" {
+            stdin().read_line(&mut rvm_code)?;
+        } else {
+            rvm_code = std::fs::read_to_string(path)?;
+        }
+        let len = rvm_code.trim_end_matches(['\n', '\r']).len();
+        rvm_code.truncate(len);
+        Ok(rvm_code)
     }
 }
 
 use self::rvm::run_rvm;
+use std::process;
 
+// Cargo builds this file as the library and `main.rs` as the binary, but the
+// host generated by rsc is compiled on its own with rustc and needs main.
+#[allow(dead_code)]
 fn main() {
-    run_rvm();
+    process::exit(run_rvm());
 }
-
-
-
-/*
-                    */
\ No newline at end of file
@@ -0,0 +1,48 @@
+(define (try thunk)
+  (call/cc
+   (lambda (k)
+     (with-exception-handler
+      (lambda (obj) (k obj))
+      thunk))))
+
+(define (show obj)
+  (write (if (error-object? obj)
+             (cons (error-object-message obj) (error-object-irritants obj))
+             obj))
+  (newline))
+
+(show (try (lambda () (raise 'oops))))
+(show (try (lambda () (%quotient 1 0))))
+(show (try (lambda () (field0 42))))
+(show (try (lambda () (+ 1 2))))
+(show (try (lambda () (+ 'a 1))))
+(show (try (lambda () (error "*** boom" 42))))
+(show (try (lambda () (with-exception-handler (lambda (obj) 'returned) (lambda () (raise 'again))))))
+
+;; The handler of the VM may take its message in a rest parameter
+(show (car (call/cc
+             (lambda (k)
+               (set-exception-handler! (lambda args (k args)))
+               (field0 42)))))
+(set-exception-handler! raise)
+
+;; Escaping from the thunk restores the handler of the continuation
+(show (call/cc
+       (lambda (k)
+         (with-exception-handler
+          (lambda (obj) (k 'first))
+          (lambda ()
+            (call/cc (lambda (k2) (with-exception-handler (lambda (obj) 'second) (lambda () (k2 #f)))))
+            (raise 'again))))))
+
+;;;options: -l max-tc
+;;;expected:
+;;;oops
+;;;("Division by zero")
+;;;("Expected a rib reference but got the number 42")
+;;;3
+;;;("*** type error" ())
+;;;("*** boom" 42)
+;;;("*** exception handler returned" again)
+;;;("Expected a rib reference but got the number 42")
+;;;first
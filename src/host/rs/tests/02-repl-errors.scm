@@ -0,0 +1,10 @@
+(export field0 + begin newline exit)
+
+(repl)
+
+;;;options: -l max-tc
+;;;input:(field0 42)(+ 1 2)(begin (newline) (exit 0))
+;;;expected:
+;;;> Expected a rib reference but got the number 42
+;;;> 3
+;;;> 
@@ -0,0 +1,52 @@
+(define (fact n)
+  (if (< n 2)
+      1
+      (* n (fact (- n 1)))))
+
+(define big (expt 2 64))
+
+(define (show x)
+  (write x)
+  (newline))
+
+(show (fact 25))
+(show (expt 2 62))
+(show (* (expt 2 62) 2))
+(show big)
+(show (- (- 0 (expt 2 62)) (expt 2 62)))
+(show (- (- 0 (expt 2 63)) 1))
+(show (quotient (fact 25) (fact 23)))
+(show (quotient (fact 25) (expt 2 40)))
+(show (quotient (- 0 big) 3))
+(show (- (+ big 1) big))
+(show (< big (* big 2)))
+(show (< (- 0 big) 0))
+(show (< 0 (- 0 big)))
+(show (= big (expt 2 64)))
+(show (eqv? big (expt 2 63)))
+(show (integer? big))
+(show (+ (- (expt 2 62) 1) 1))
+(show (quotient (- 0 (expt 2 62)) -1))
+(show (- (expt 2 62) 1))
+
+;;;options: -l max-tc
+;;;expected:
+;;;15511210043330985984000000
+;;;4611686018427387904
+;;;9223372036854775808
+;;;18446744073709551616
+;;;-9223372036854775808
+;;;-9223372036854775809
+;;;600
+;;;14107363352496
+;;;-6148914691236517205
+;;;1
+;;;#t
+;;;#t
+;;;#f
+;;;#t
+;;;#f
+;;;#t
+;;;4611686018427387904
+;;;4611686018427387904
+;;;4611686018427387903
@@ -0,0 +1,41 @@
+(export / + - * quotient < = eqv? expt exact->inexact inexact->exact sqrt round truncate floor ceiling integer? exact? number? rib? number->string begin newline exit)
+
+(repl)
+
+;;;options: -l max-tc
+;;;input:(/ 1. 3)(/ 6 3)(/ 1 4)(+ 1.5 2)(- 1 2.5)(* (expt 2 64) .5)(quotient 7. 2)(< 1 1.5)(< 2.5 (expt 2 64))(= 2 2.)(eqv? 2 2.)(eqv? .5 (/ 1 2))(exact->inexact 3)(inexact->exact 3.)(inexact->exact 1e20)(sqrt 16)(round 2.5)(round 3.5)(round 7)(truncate -2.7)(floor -2.7)(ceiling 2.2)(integer? 2.)(integer? 2.5)(exact? 2.5)-1.25e2(number->string .1)(/ 1. 0.)(rib? 2.5)(rib? (expt 2 64))(number? 2.5)(number? (expt 2 64))(rib? "2.5")(begin (newline) (exit 0))
+;;;expected:
+;;;> 0.3333333333333333
+;;;> 2
+;;;> 0.25
+;;;> 3.5
+;;;> -1.5
+;;;> 9.223372036854776e18
+;;;> 3.0
+;;;> #t
+;;;> #t
+;;;> #t
+;;;> #f
+;;;> #t
+;;;> 3.0
+;;;> 3
+;;;> 100000000000000000000
+;;;> 4.0
+;;;> 2.0
+;;;> 4.0
+;;;> 7
+;;;> -2.0
+;;;> -3.0
+;;;> 3.0
+;;;> #t
+;;;> #f
+;;;> #f
+;;;> -125.0
+;;;> "0.1"
+;;;> +inf.0
+;;;> #f
+;;;> #f
+;;;> #t
+;;;> #t
+;;;> #t
+;;;> 
@@ -0,0 +1,19 @@
+(define (echo-codes)
+  (let ((c (read-char)))
+    (if (not (eof-object? c))
+        (begin
+          (write (char->integer c))
+          (newline)
+          (echo-codes)))))
+
+(echo-codes)
+
+;;;options: -l max-tc
+;;;input:aé日本😀
+;;;expected:
+;;;97
+;;;233
+;;;26085
+;;;26412
+;;;128512
+;;;10
@@ -0,0 +1,8 @@
+(display "before")
+(flush-output)
+(display " after")
+(newline)
+
+;;;options: -l max-tc
+;;;expected:
+;;;before after
@@ -0,0 +1,21 @@
+;; Foreign objects are made by the host, this one is forged with rib
+(define f (rib 0 0 6))
+
+(display (foreign? f))
+(newline)
+(display (foreign? 42))
+(newline)
+(display (foreign? "foreign"))
+(newline)
+(display (foreign? (cons f '())))
+(newline)
+(write (cons f (cons 1 '())))
+(newline)
+
+;;;options: -l max-tc
+;;;expected:
+;;;#t
+;;;#f
+;;;#f
+;;;#f
+;;;(#<foreign> 1)
@@ -0,0 +1,49 @@
+;; Objects only reachable through weak references die at the next collection
+(define kept (cons 1 (cons 2 '())))
+(define key (cons 3 (cons 4 '())))
+
+(define kept-box (make-weak-box kept))
+(define dead-box (make-weak-box (cons 5 (cons 6 '()))))
+(define key-box (make-weak-box key))
+
+(define table (make-weak-table))
+(weak-table-set! table kept 'kept)
+(weak-table-set! table key (cons key '())) ;; the value does not keep its key
+
+(define guardian (make-guardian))
+(guardian-register! guardian kept)
+(guardian-register! guardian (cons 7 (cons 8 '())))
+
+(set! key #f)
+
+(define (churn n acc)
+  (if (= n 0)
+      acc
+      (churn (- n 1) (cons n (if (pair? acc) (cdr acc) acc)))))
+
+(churn 200000 '())
+
+(display (weak-box-value kept-box))
+(newline)
+(display (weak-box-value dead-box))
+(newline)
+(display (weak-box-value key-box))
+(newline)
+(display (weak-table-ref table kept 'none))
+(newline)
+(display (guardian-next guardian))
+(newline)
+(display (guardian-next guardian))
+(newline)
+(display (cons dead-box (cons table (cons guardian '()))))
+(newline)
+
+;;;options: -l max-tc
+;;;expected:
+;;;(1 2)
+;;;#f
+;;;#f
+;;;kept
+;;;(7 8)
+;;;#f
+;;;(#<weak-box> #<weak-table> #<guardian>)
@@ -0,0 +1,51 @@
+;; Old ribs written with references to young ones, across minor and major
+;; collections
+(define (churn n acc)
+  (if (= n 0)
+      acc
+      (churn (- n 1) (cons n (if (pair? acc) (cdr acc) acc)))))
+
+(define (iota n acc) (if (= n 0) acc (iota (- n 1) (cons n acc))))
+
+(define (sum lst) (if (pair? lst) (+ (car lst) (sum (cdr lst))) 0))
+
+(define vect (make-vector 3))
+(define cell (cons 0 '()))
+(define global #f)
+(define kept '())
+
+(churn 30000 '()) ;; vect and cell are now old
+
+(define (loop i)
+  (if (< i 12)
+      (begin
+        (vector-set! vect (modulo i 3) (iota 100 '()))
+        (set-cdr! cell (cons i (cdr cell)))
+        (set! global (iota 50 '()))
+        (set! kept (cons (iota 2000 '()) kept)) ;; grows the old generation
+        (churn 20000 '())
+        (loop (+ i 1)))))
+
+(loop 0)
+
+(display (sum (vector-ref vect 0)))
+(newline)
+(display (sum (vector-ref vect 2)))
+(newline)
+(display (sum (cdr cell)))
+(newline)
+(display (sum global))
+(newline)
+(display (length kept))
+(newline)
+(display (sum (car kept)))
+(newline)
+
+;;;options: -l max-tc
+;;;expected:
+;;;5050
+;;;5050
+;;;66
+;;;1275
+;;;12
+;;;2001000
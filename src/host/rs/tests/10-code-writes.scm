@@ -0,0 +1,21 @@
+;; The operations of the program are decoded once, but writing their ribs
+;; changes what is executed
+
+(define (f) 42)
+
+(define (first-operation procedure)
+  (field2 (field0 procedure)))
+
+(display (f))
+(newline)
+(display (field1 (first-operation f)))
+(newline)
+(field1-set! (first-operation f) 99)
+(display (f))
+(newline)
+
+;;;options: -l max-tc
+;;;expected:
+;;;42
+;;;42
+;;;99
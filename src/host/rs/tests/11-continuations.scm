@@ -0,0 +1,31 @@
+;; The values pushed since the last call are not ribs until a continuation
+;; captures the stack, so resuming it must find them again
+
+(define k #f)
+(define n 0)
+
+(display (+ 100 (* 10 (call/cc (lambda (c) (set! k c) 1)))))
+(newline)
+(set! n (+ n 1))
+(if (< n 3) (k (+ n 1)))
+
+(define (deep n return)
+  (if (= n 0) (return 'bottom) (+ 1 (deep (- n 1) return))))
+
+(display (call/cc (lambda (return) (deep 10000 return))))
+(newline)
+
+(define r '())
+(define kk #f)
+(set! r (cons (call/cc (lambda (c) (set! kk c) 'a)) r))
+(if (< (length r) 3) (kk 'b))
+(display r)
+(newline)
+
+;;;options: -l max-tc
+;;;expected:
+;;;110
+;;;120
+;;;130
+;;;bottom
+;;;(b b a)
@@ -0,0 +1,59 @@
+;; string->symbol, equal?, string-append, list->string, number->string and
+;; display of strings are primitives of the Rust host
+
+(define s (string-append "brand" (string-append "-" "new")))
+(display s)
+(newline)
+(display (eq? (string->symbol s) (string->symbol "brand-new")))
+(display (eq? (string->symbol "car") 'car))
+(display (eq? (string->symbol "nil") 'nil))
+(newline)
+
+(display (equal? '(1 (2 "two") #(3 x)) (cons 1 (cons (cons 2 (cons "two" '())) (cons (list->vector (cons 3 (cons 'x '()))) '())))))
+(display (equal? '(1 (2 "two")) '(1 (2 "too"))))
+(display (equal? "" ""))
+(display (equal? (exact->inexact 1) (exact->inexact 1)))
+(display (equal? (* 99999999999 99999999999) (* 99999999999 99999999999)))
+(newline)
+
+(define (iota n acc) (if (= n 0) acc (iota (- n 1) (cons n acc))))
+(display (equal? (iota 100000 '()) (iota 100000 '())))
+(newline)
+
+(define chars (cons 97 (cons 98 (cons 99 '()))))
+(define str (list->string chars))
+(display (string-length str))
+(set-car! chars 65) ;; the string shares the list
+(display str)
+(newline)
+
+(display (number->string -1234))
+(display " ")
+(display (number->string (* 99999999999 99999999999)))
+(display " ")
+(display (number->string (/ 1 4)))
+(newline)
+
+(write "a\"b")
+(display 'sym)
+(display " end\n")
+
+(define (try thunk)
+  (call/cc (lambda (k) (with-exception-handler k thunk))))
+
+(display (error-object-message (try (lambda () (string-append "a" 5)))))
+(newline)
+(display (error-object-message (try (lambda () (list->string (cons 1 (cons 'a '())))))))
+(newline)
+
+;;;options: -l max-tc
+;;;expected:
+;;;brand-new
+;;;#t#t#t
+;;;#t#f#t#t#t
+;;;#t
+;;;3Abc
+;;;-1234 9999999999800000000001 0.25
+;;;"a\"b"sym end
+;;;Expected a string
+;;;Expected a list of characters
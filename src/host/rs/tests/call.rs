@@ -0,0 +1,39 @@
+use ribbit::rvm::{RibField, RunStatus, Vm, VmError};
+
+mod common;
+
+// Runs SPINNING_HANDLER until it wrote A, after its definitions
+fn start() -> Vm {
+    let mut vm = common::load(common::SPINNING_HANDLER);
+    while vm.take_output().unwrap().is_empty() {
+        assert_eq!(vm.run_for(1).unwrap(), RunStatus::OutOfFuel);
+    }
+    vm
+}
+
+#[test]
+fn calls_procedure_of_paused_program() {
+    let seven = RibField::fixnum(7).unwrap();
+    let mut vm = start();
+    assert_eq!(vm.call("ident", &[seven]), Ok(seven));
+    assert_eq!(vm.call("spin", &[seven]), Ok(RibField::fixnum(0).unwrap()));
+    assert_eq!(vm.call("ident", &[]),
+               Err(VmError::ArityMismatch { nargs: 0, expected_nargs: 1, variadic: false }));
+    assert_eq!(vm.run(), Err(VmError::TypeError(String::from(common::FIELD0_OF_42))));
+    assert_eq!(vm.take_output().unwrap(), "");
+}
+
+#[test]
+fn keeps_error_of_running_handler() {
+    let seven = RibField::fixnum(7).unwrap();
+    let mut vm = start();
+    // Calls made while the handler spins must not make the program forget
+    // the error it stops with once the handler returns
+    let result = loop {
+        match vm.run_for(1) {
+            Ok(RunStatus::OutOfFuel) => assert_eq!(vm.call("ident", &[seven]), Ok(seven)),
+            result => break result,
+        }
+    };
+    assert_eq!(result, Err(VmError::TypeError(String::from(common::FIELD0_OF_42))));
+}
@@ -0,0 +1,153 @@
+//! The programs run by the integration tests. The RVM code of each was
+//! compiled from the Scheme code in its doc comment with
+//! `rsc -t rvm -f+ arity-check -l empty`, and ends with `(putchar 10)` when
+//! it would otherwise be a lone primitive call, which rsc leaves out.
+
+#![allow(dead_code)]
+
+use ribbit::rvm::Vm;
+
+/// Loads the program `rvm_code` in a new VM whose output is captured.
+pub fn load(rvm_code: &str) -> Vm {
+    let mut vm = Vm::new();
+    vm.load(rvm_code).unwrap();
+    vm.capture_output().unwrap();
+    vm
+}
+
+/// ```scheme
+/// (define (echo)
+///   (let ((c (getchar)))
+///     (if (< c 0) #f (begin (putchar c) (echo)))))
+/// (echo)
+/// ```
+pub const ECHO: &str = "#rahcteg,<,di,rahctup,1gra,ohce,,,,;'k!''k?m@l^*li$~Bmk^Cky!*:nlkl!(:nlkm!+:nlkv0!,:nlkv5!):nlkv6{";
+
+/// ```scheme
+/// (define (count n)
+///   (if (< n 10) (begin (putchar (+ 48 n)) (count (+ n 1))) #f))
+/// (count 0)
+/// ```
+pub const COUNT: &str = "#<,rahctup,1gra,di,+,tnuoc,,,,;'lk!')li$'l?ml^AmBl?m_vR%~Cmu^{!):nlkl!*:nlkm!,:nlkv0!(:nlkv1!+:nlkv6{";
+
+/// ```scheme
+/// (define (grow lst) (grow (rib lst lst 0)))
+/// (grow 0)
+/// ```
+pub const GROW: &str = "#worg,,,,bir;'lk!''l:nk_^{{";
+
+/// ```scheme
+/// (define (churn n)
+///   (if (< 0 n) (begin (rib n n 0) (churn (- n 1))) #f))
+/// (churn 3000)
+/// (putchar 65)
+/// (putchar 10)
+/// ```
+pub const CHURN: &str = "#<,-,di,nruhc,rahctup,1gra,,,,bir;(lu>m?lvR6>m@lvRd-!)*li$)lBml^>m:nk_^~Cm_k{!*:nlkl!':nlkm!,:nlkv0!+:nlkv2!(:nlkv6{";
+
+/// ```scheme
+/// (putchar (add3 30 1 2))
+/// (putchar 10)
+/// ```
+pub const ADD3: &str = "#3dda,1gra,rahctup,,,,;'lu?m>l@nmlvA!(:nlkm!':nlkv6{";
+
+/// ```scheme
+/// (putchar (add3 30 1))
+/// (putchar 10)
+/// ```
+pub const ADD3_MISSING_ARGUMENT: &str = "#3dda,1gra,rahctup,,,,;'lu?m>l@mlvA!(:nlkm!':nlkv6{";
+
+/// ```scheme
+/// (putchar (add3 30 1 2 3))
+/// (putchar 10)
+/// ```
+pub const ADD3_EXTRA_ARGUMENT: &str = "#3dda,1gra,rahctup,,,,;'lu?m>l@onmlvA!(:nlkm!':nlkv6{";
+
+/// A call to a primitive that does not exist.
+///
+/// ```scheme
+/// ((rib 1000 0 1) 5)
+/// (putchar 10)
+/// ```
+pub const UNKNOWN_PRIMITIVE: &str = "#2gra,1gra,rahctup,,,,bir;'lu?m@mX%lp:nlkvfE!(:nlkm!):nlkn!':nlkv6{";
+
+/// Calls to the host primitives `keep` and `check`, with enough garbage in
+/// between for collections.
+///
+/// ```scheme
+/// (define (churn n)
+///   (if (< 0 n) (begin (rib n n 0) (churn (- n 1))) #f))
+/// (keep)
+/// (churn 100000)
+/// (check)
+/// (putchar 10)
+/// ```
+pub const KEEP: &str = "#<,-,di,peek,kcehc,rahctup,nruhc,1gra,,,,bir;)lu>mAk>m?lvRR!M>mBk!(,li$(lDml^>m:nk_^~Em_k{!,:nlkl!':nlkm!.:nlkv0!-:nlkv2!):nlkv6{";
+
+/// ```scheme
+/// (convert)
+/// (putchar 10)
+/// ```
+pub const CONVERT: &str = "#trevnoc,1gra,rahctup,,,,;'lu?m@k!(:nlkm!':nlkv6{";
+
+/// ```scheme
+/// (allocate)
+/// (putchar 10)
+/// ```
+pub const ALLOCATE: &str = "#etacolla,1gra,rahctup,,,,;'lu?m@k!(:nlkm!':nlkv6{";
+
+/// ```scheme
+/// (define result (+ config 1))
+/// (putchar result)
+/// ```
+pub const CONFIGURED: &str = "#gifnoc,tluser,+,rahctup,,,,;'li)!)?mli*!(:nlkv1!':nlkv6{";
+
+/// The primitive `set-exception-handler!` is number 21, which the code of
+/// the rvm target can only reach by making the procedure itself.
+///
+/// ```scheme
+/// (define (handler obj) (report (field0 obj) (field1 obj) (field2 obj)))
+/// ((rib 21 0 1) handler)
+/// (putchar (field0 42))
+/// (putchar 10)
+/// ```
+pub const HANDLER_RETURNS: &str = "#reldnah,1dleif,2dleif,troper,2gra,0dleif,1gra,rahctup,,,,bir;'lu?m>l@lvM?mAmX%li.:nlkv8!.+nCl`Dl_@l^{!(:nlkm!*:nlkn!):nlkq!-:nlkr!,:nlks!':nlkv6{";
+
+/// ```scheme
+/// ((rib 21 0 1) 5)
+/// (putchar 10)
+/// ```
+pub const HANDLER_NOT_PROCEDURE: &str = "#2gra,1gra,rahctup,,,,bir;'lu?m@mX%lp:nlkv8!(:nlkm!):nlkn!':nlkv6{";
+
+/// ```scheme
+/// (define (handler a b) 0)
+/// ((rib 21 0 1) handler)
+/// (putchar 10)
+/// ```
+pub const HANDLER_TWO_PARAMETERS: &str = "#reldnah,di,2gra,1gra,rahctup,,,,bir;'lu?m@mX%li+:nlkv8!+*lk}'!*:nlkl!(:nlkm!):nlkn!':nlkv6{";
+
+/// ```scheme
+/// ((rib 21 0 1) #f)
+/// (putchar (field0 42))
+/// ```
+pub const HANDLER_REMOVED: &str = "#2gra,1gra,0dleif,rahctup,,,,bir;'l?lvM@mAmX%li$:nlkv8!):nlkm!*:nlkn!(:nlkq!':nlkv6{";
+
+/// ```scheme
+/// (define (spin n) (if (< 0 n) (spin (- n 1)) n))
+/// (define (handler obj) (spin 100))
+/// (define (ident x) x)
+/// ((rib 21 0 1) handler)
+/// (putchar (ident 65))
+/// (putchar (field0 42))
+/// (putchar 10)
+/// ```
+pub const SPINNING_HANDLER: &str = "#reldnah,<,-,tnedi,2gra,0dleif,nips,di,1gra,rahctup,,,,bir;'lu?m>lBlvM?m>lDlvR6?mCmX%li0:nlkv8!-)l^{!0*lvS+{!*)l^*lEml^~Fm_k{!):nlkl!(:nlkm!,:nlkn!+:nlkq!/:nlkv0!.:nlkv2!':nlkv6{";
+
+/// ```scheme
+/// (putchar (field0 42))
+/// (putchar 10)
+/// ```
+pub const FIELD0: &str = "#0dleif,1gra,rahctup,,,,;'lu?m>l@lvM!(:nlkm!):nlkq!':nlkv6{";
+
+/// The error of `(field0 42)`.
+pub const FIELD0_OF_42: &str = "Expected a rib reference but got the number 42";
@@ -0,0 +1,89 @@
+use std::fmt::Debug;
+
+use ribbit::rvm::{FromScheme, RibField, RibHeap, ToScheme, Vector, VmError, MAX_FIXNUM, MIN_FIXNUM};
+
+mod common;
+
+// Runs test on the heap of a VM, from the host primitive `convert` of CONVERT
+fn with_heap(test: impl FnOnce(&mut RibHeap) + 'static) {
+    let mut vm = common::load(common::CONVERT);
+    let mut test = Some(test);
+    vm.register_primitive("convert", 0, move |_args, heap| {
+        test.take().unwrap()(heap);
+        Ok(RibField::fixnum(0).unwrap())
+    }).unwrap();
+    vm.run().unwrap();
+}
+
+fn round_trip<T: ToScheme + FromScheme + PartialEq + Debug>(heap: &mut RibHeap, value: T) {
+    let scheme = value.to_scheme(heap);
+    assert_eq!(T::from_scheme(&scheme, heap), Ok(value));
+}
+
+#[test]
+fn integers_round_trip() {
+    with_heap(|heap| {
+        for n in [0, 1, -1, MAX_FIXNUM, MIN_FIXNUM, MAX_FIXNUM + 1, MIN_FIXNUM - 1, i64::MAX, i64::MIN] {
+            round_trip(heap, n);
+        }
+        for n in [0, MAX_FIXNUM as u64, MAX_FIXNUM as u64 + 1, u64::MAX] {
+            round_trip(heap, n);
+        }
+        round_trip(heap, i8::MIN);
+        round_trip(heap, u8::MAX);
+        round_trip(heap, i32::MIN);
+        round_trip(heap, u32::MAX);
+        round_trip(heap, isize::MIN);
+        round_trip(heap, usize::MAX);
+    });
+}
+
+#[test]
+fn large_integers_are_bignums() {
+    with_heap(|heap| {
+        assert_eq!(MAX_FIXNUM.to_scheme(heap).as_number(), Some(MAX_FIXNUM));
+        assert_eq!(RibField::fixnum(MAX_FIXNUM + 1), None);
+        assert!((MAX_FIXNUM + 1).to_scheme(heap).is_rib());
+        assert!(i64::MIN.to_scheme(heap).is_rib());
+        assert!(u64::MAX.to_scheme(heap).is_rib());
+    });
+}
+
+#[test]
+fn rejects_integers_out_of_range() {
+    with_heap(|heap| {
+        let big = 300.to_scheme(heap);
+        assert!(matches!(u8::from_scheme(&big, heap), Err(VmError::TypeError(_))));
+        let negative = (-1).to_scheme(heap);
+        assert!(matches!(u64::from_scheme(&negative, heap), Err(VmError::TypeError(_))));
+        let huge = u64::MAX.to_scheme(heap);
+        assert!(matches!(i64::from_scheme(&huge, heap), Err(VmError::TypeError(_))));
+    });
+}
+
+#[test]
+fn other_values_round_trip() {
+    with_heap(|heap| {
+        round_trip(heap, true);
+        round_trip(heap, false);
+        round_trip(heap, 0.1);
+        round_trip(heap, -1e300);
+        round_trip(heap, 'λ');
+        round_trip(heap, String::from("héllo \"world\"\n"));
+        round_trip(heap, String::new());
+        round_trip(heap, vec![1, MAX_FIXNUM + 1, -3]);
+        round_trip(heap, vec![vec![true], vec![], vec![false, true]]);
+        round_trip(heap, Vector(vec![String::from("a"), String::from("bc")]));
+        round_trip(heap, Vector(Vec::<i64>::new()));
+    });
+}
+
+#[test]
+fn lists_are_not_vectors() {
+    with_heap(|heap| {
+        let list = vec![1, 2].to_scheme(heap);
+        assert!(matches!(Vector::<i64>::from_scheme(&list, heap), Err(VmError::TypeError(_))));
+        let vector = Vector(vec![1, 2]).to_scheme(heap);
+        assert!(matches!(Vec::<i64>::from_scheme(&vector, heap), Err(VmError::TypeError(_))));
+    });
+}
@@ -0,0 +1,59 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ribbit::rvm::{FromScheme, RibField, Vm, VmError};
+
+mod common;
+
+#[test]
+fn handler_receives_error_object() {
+    let reported = Rc::new(RefCell::new(None));
+    let mut vm = common::load(common::HANDLER_RETURNS);
+    let into = reported.clone();
+    vm.register_primitive("report", 3, move |args, heap| {
+        let message = heap.get_string(&args[0])?;
+        let irritants = Vec::<RibField>::from_scheme(&args[1], heap)?;
+        *into.borrow_mut() = Some((message, irritants.len(), args[2].as_number()));
+        Ok(args[0])
+    }).unwrap();
+    // The handler returns, which stops the program with the error
+    assert_eq!(vm.run(), Err(VmError::TypeError(String::from(common::FIELD0_OF_42))));
+    assert_eq!(reported.borrow_mut().take(), Some((String::from(common::FIELD0_OF_42), 0, Some(14))));
+    assert_eq!(vm.take_output().unwrap(), "");
+}
+
+#[test]
+fn forgets_handler_of_previous_program() {
+    let reported = Rc::new(RefCell::new(false));
+    let mut vm = common::load(common::HANDLER_RETURNS);
+    let into = reported.clone();
+    vm.register_primitive("report", 3, move |args, _heap| {
+        *into.borrow_mut() = true;
+        Ok(args[0])
+    }).unwrap();
+    assert!(vm.run().is_err());
+    assert!(reported.replace(false));
+    // FIELD0 does not install a handler, so its error is not reported
+    vm.load(common::FIELD0).unwrap();
+    assert_eq!(vm.run(), Err(VmError::TypeError(String::from(common::FIELD0_OF_42))));
+    assert!(!*reported.borrow());
+}
+
+#[test]
+fn rejects_handler_that_is_not_a_procedure() {
+    let mut vm = common::load(common::HANDLER_NOT_PROCEDURE);
+    assert!(matches!(vm.run(), Err(VmError::RejectedHandler(_))));
+}
+
+#[test]
+fn rejects_handler_that_does_not_take_one_argument() {
+    let mut vm = common::load(common::HANDLER_TWO_PARAMETERS);
+    assert!(matches!(vm.run(), Err(VmError::RejectedHandler(_))));
+}
+
+#[test]
+fn accepts_false_as_no_handler() {
+    let mut vm = Vm::new();
+    vm.load(common::HANDLER_REMOVED).unwrap();
+    assert_eq!(vm.run(), Err(VmError::TypeError(String::from(common::FIELD0_OF_42))));
+}
@@ -0,0 +1,104 @@
+use ribbit::rvm::{RibField, RunStatus, Vm, VmError};
+
+mod common;
+
+// The registers of the program: the next operation, the stack and the
+// global variables
+fn registers(vm: &mut Vm) -> (usize, Vec<RibField>, Vec<(String, RibField)>) {
+    (vm.pc(), vm.stack(), vm.globals().unwrap())
+}
+
+// Runs vm by slices of fuel and reference by slices of step, a multiple of
+// fuel, and checks that they agree each time both stopped
+fn run_along(vm: &mut Vm, reference: &mut Vm, fuel: u64, step: u64) {
+    loop {
+        let mut status = Ok(RunStatus::OutOfFuel);
+        for _ in 0..step / fuel {
+            status = vm.run_for(fuel);
+            if status != Ok(RunStatus::OutOfFuel) {
+                break
+            }
+        }
+        assert_eq!(status, reference.run_for(step));
+        assert_eq!(vm.take_output().unwrap(), reference.take_output().unwrap());
+        assert!(registers(vm) == registers(reference), "fuel {}", fuel);
+        if status != Ok(RunStatus::OutOfFuel) {
+            return
+        }
+    }
+}
+
+#[test]
+fn resumes_after_running_out_of_fuel() {
+    let mut vm = common::load(common::COUNT);
+    let mut slices = Vec::new();
+    loop {
+        match vm.run_for(10).unwrap() {
+            RunStatus::OutOfFuel => slices.push(vm.take_output().unwrap()),
+            status => {
+                assert_eq!(status, RunStatus::Halted);
+                slices.push(vm.take_output().unwrap());
+                break
+            }
+        }
+    }
+    assert!(slices.len() > 5);
+    assert_eq!(slices.concat(), "0123456789");
+    assert_eq!(vm.run_for(10).unwrap(), RunStatus::Halted);
+}
+
+#[test]
+fn runs_nothing_without_fuel() {
+    let mut vm = common::load(common::COUNT);
+    assert_eq!(vm.run_for(0).unwrap(), RunStatus::OutOfFuel);
+    assert_eq!(vm.take_output().unwrap(), "");
+    assert_eq!(vm.run().unwrap(), RunStatus::Halted);
+    assert_eq!(vm.take_output().unwrap(), "0123456789");
+}
+
+#[test]
+fn registers_survive_running_out_of_fuel() {
+    for fuel in [1, 2, 3, 7] {
+        run_along(&mut common::load(common::COUNT), &mut common::load(common::COUNT), fuel, 7 * fuel);
+    }
+}
+
+#[test]
+fn registers_survive_running_out_of_fuel_during_collections() {
+    // A small heap makes the incremental collections frequent, and each
+    // one lasts several operations
+    let churn = || {
+        let mut vm = common::load(common::CHURN);
+        vm.set_max_heap_size(Some(2_000));
+        vm.set_incremental_gc(true);
+        vm.set_heap_growth(0);
+        vm
+    };
+    run_along(&mut churn(), &mut churn(), 1, 1_000);
+}
+
+#[test]
+fn stops_when_the_heap_is_exhausted() {
+    let mut vm = Vm::new();
+    vm.load(common::GROW).unwrap();
+    vm.set_max_heap_size(Some(10_000));
+    assert_eq!(vm.run(), Err(VmError::HeapExhausted));
+    vm.set_max_heap_size(Some(100_000));
+    assert_eq!(vm.run_for(10_000).unwrap(), RunStatus::OutOfFuel);
+    assert_eq!(vm.run(), Err(VmError::HeapExhausted));
+}
+
+#[test]
+fn stops_when_a_primitive_exhausts_the_heap() {
+    for (max_size, result) in [(10_000, Err(VmError::HeapExhausted)), (100_000, Ok(RunStatus::Halted))] {
+        let mut vm = common::load(common::ALLOCATE);
+        // The string is garbage once the call returns, so only the call
+        // itself takes the heap past its maximum size
+        vm.register_primitive("allocate", 0, |_args, heap| {
+            heap.make_string(&"x".repeat(20_000));
+            Ok(RibField::fixnum(0).unwrap())
+        }).unwrap();
+        vm.set_max_heap_size(Some(max_size));
+        assert_eq!(vm.run(), result);
+    }
+}
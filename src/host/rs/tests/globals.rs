@@ -0,0 +1,50 @@
+use ribbit::rvm::{RibField, RunStatus, Vm, VmError};
+
+mod common;
+
+fn number(n: i64) -> RibField {
+    RibField::fixnum(n).unwrap()
+}
+
+#[test]
+fn program_reads_and_writes_globals() {
+    let mut vm = common::load(common::CONFIGURED);
+    vm.set_global("config", number(64)).unwrap();
+    assert_eq!(vm.run().unwrap(), RunStatus::Halted);
+    assert_eq!(vm.take_output().unwrap(), "A");
+    assert_eq!(vm.get_global("result"), Ok(number(65)));
+    assert_eq!(vm.get_global("config"), Ok(number(64)));
+}
+
+#[test]
+fn gets_what_was_set() {
+    let mut vm = common::load(common::CONFIGURED);
+    vm.set_global("result", number(1)).unwrap();
+    assert_eq!(vm.get_global("result"), Ok(number(1)));
+    // A global the program does not have is added
+    vm.set_global("added", number(2)).unwrap();
+    assert_eq!(vm.get_global("added"), Ok(number(2)));
+    vm.set_global("added", number(3)).unwrap();
+    assert_eq!(vm.get_global("added"), Ok(number(3)));
+}
+
+#[test]
+fn rejects_unknown_globals() {
+    let mut vm = Vm::new();
+    assert_eq!(vm.get_global("config"), Err(VmError::UnknownGlobal(String::from("config"))));
+    assert_eq!(vm.set_global("config", number(0)), Err(VmError::UnknownGlobal(String::from("config"))));
+    vm.load(common::CONFIGURED).unwrap();
+    assert_eq!(vm.get_global("nothing"), Err(VmError::UnknownGlobal(String::from("nothing"))));
+}
+
+#[test]
+fn lists_named_globals() {
+    let mut vm = common::load(common::CONFIGURED);
+    vm.set_global("config", number(64)).unwrap();
+    vm.run().unwrap();
+    let globals = vm.globals().unwrap();
+    assert!(globals.contains(&(String::from("config"), number(64))));
+    assert!(globals.contains(&(String::from("result"), number(65))));
+    assert!(globals.iter().any(|(name, _)| name == "putchar"));
+    assert!(globals.iter().all(|(name, _)| !name.is_empty()));
+}
@@ -0,0 +1,92 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ribbit::rvm::{Handle, RibField, Vm, VmError};
+
+mod common;
+
+// What the primitives of KEEP saw: the handle of the string that keep
+// rooted, the string as it was made, and what check read through the handle
+#[derive(Default)]
+struct Kept {
+    handle: Option<Handle>,
+    made: Option<RibField>,
+    checked: Option<String>,
+}
+
+// Runs KEEP on vm, with a keep primitive that roots a new string and a
+// check primitive that reads it back after the collections
+fn run_keep(vm: &mut Vm) -> Kept {
+    let kept = Rc::new(RefCell::new(Kept::default()));
+    vm.load(common::KEEP).unwrap();
+    let into = kept.clone();
+    vm.register_primitive("keep", 0, move |_args, heap| {
+        let s = heap.make_string("kept");
+        let mut kept = into.borrow_mut();
+        kept.handle = Some(heap.root(s));
+        kept.made = Some(s);
+        Ok(s)
+    }).unwrap();
+    let from = kept.clone();
+    vm.register_primitive("check", 0, move |_args, heap| {
+        let mut kept = from.borrow_mut();
+        let s = heap.rooted(kept.handle.as_ref().unwrap())?;
+        kept.checked = Some(heap.get_string(&s)?);
+        Ok(s)
+    }).unwrap();
+    vm.capture_output().unwrap();
+    vm.run().unwrap();
+    kept.take()
+}
+
+// Runs KEEP on vm and checks that the string kept its contents and is
+// where a collection moved it
+fn check_kept(vm: &mut Vm) {
+    let kept = run_keep(vm);
+    assert_eq!(kept.checked.as_deref(), Some("kept"));
+    let handle = kept.handle.unwrap();
+    let moved = vm.rooted(&handle).unwrap();
+    assert_ne!(Some(moved), kept.made); // a collection copied it
+    assert_eq!(vm.unroot(handle), Ok(moved));
+}
+
+#[test]
+fn rooted_value_survives_collections() {
+    let mut vm = Vm::new();
+    check_kept(&mut vm);
+}
+
+#[test]
+fn rooted_value_survives_major_collections() {
+    let mut vm = Vm::new();
+    vm.set_heap_growth(0);
+    check_kept(&mut vm);
+}
+
+#[test]
+fn rooted_value_survives_incremental_collections() {
+    let mut vm = Vm::new();
+    vm.set_incremental_gc(true);
+    vm.set_heap_growth(0);
+    check_kept(&mut vm);
+}
+
+#[test]
+fn rejects_released_handles() {
+    let mut vm = Vm::new();
+    let value = vm.heap().make_string("released");
+    let handle = vm.root(value);
+    vm.reset();
+    assert_eq!(vm.rooted(&handle), Err(VmError::UnknownHandle));
+    assert_eq!(vm.unroot(handle), Err(VmError::UnknownHandle));
+}
+
+#[test]
+fn rejects_handles_of_another_vm() {
+    let mut vm = Vm::new();
+    let mut other = Vm::new();
+    let value = other.heap().make_string("elsewhere");
+    let handle = other.root(value);
+    assert_eq!(vm.rooted(&handle), Err(VmError::UnknownHandle));
+    assert_eq!(vm.unroot(handle), Err(VmError::UnknownHandle));
+}
@@ -0,0 +1,50 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use ribbit::rvm::{RunStatus, Vm};
+
+mod common;
+
+#[derive(Clone, Default)]
+struct SharedLog(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn captures_output_of_in_memory_input() {
+    let mut vm = common::load(common::ECHO);
+    vm.set_input(&b"hello, world\n"[..]);
+    assert_eq!(vm.run().unwrap(), RunStatus::Halted);
+    assert_eq!(vm.take_output().unwrap(), "hello, world\n");
+    assert_eq!(vm.take_output().unwrap(), "");
+}
+
+#[test]
+fn reads_and_writes_utf8() {
+    let mut vm = common::load(common::ECHO);
+    vm.set_input(io::Cursor::new(String::from("héllo λ")));
+    vm.run().unwrap();
+    assert_eq!(vm.take_output().unwrap(), "héllo λ");
+}
+
+#[test]
+fn writes_to_given_output() {
+    let log = SharedLog::default();
+    let mut vm = Vm::new();
+    vm.load(common::ECHO).unwrap();
+    vm.set_input(&b"logged"[..]);
+    vm.set_output(log.clone()).unwrap();
+    vm.run().unwrap();
+    assert_eq!(&*log.0.borrow(), b"logged");
+    assert_eq!(vm.take_output().unwrap(), "");
+}
@@ -0,0 +1,175 @@
+// Generated by `rvm --emit-rust`, see `ribbit::rvm::translate`.
+
+use ribbit::rvm::{Native, NativeBlock, Vm, VmError};
+
+/// Loads the program in `vm` with its native code.
+pub fn load(vm: &mut Vm) -> Result<(), VmError> {
+    vm.load(RVM_CODE)?;
+    vm.set_native_code(&BLOCKS)
+}
+
+pub const RVM_CODE: &str = "#<,-,di,0dleif,tnuoc,rahctup,1gra,,,,;(lu>m?lAlvM>m?lvR6>m@lp!)+li$)lCml^>m?lvR#~Dm_k{!+:nlkl!':nlkm!*:nlkq!-:nlkv0!,:nlkv2!(:nlkv6{";
+
+static BLOCKS: [NativeBlock; 21] = [
+    NativeBlock { run: block_0, len: 5 },
+    NativeBlock { run: block_1, len: 6 },
+    NativeBlock { run: block_2, len: 6 },
+    NativeBlock { run: block_3, len: 6 },
+    NativeBlock { run: block_4, len: 6 },
+    NativeBlock { run: block_5, len: 6 },
+    NativeBlock { run: block_6, len: 6 },
+    NativeBlock { run: block_7, len: 4 },
+    NativeBlock { run: block_8, len: 2 },
+    NativeBlock { run: block_9, len: 1 },
+    NativeBlock { run: block_10, len: 3 },
+    NativeBlock { run: block_11, len: 3 },
+    NativeBlock { run: block_12, len: 3 },
+    NativeBlock { run: block_13, len: 2 },
+    NativeBlock { run: block_14, len: 2 },
+    NativeBlock { run: block_15, len: 3 },
+    NativeBlock { run: block_16, len: 4 },
+    NativeBlock { run: block_17, len: 2 },
+    NativeBlock { run: block_18, len: 2 },
+    NativeBlock { run: block_19, len: 2 },
+    NativeBlock { run: block_20, len: 3 },
+];
+
+fn block_0(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(19);
+    n.push_number(0);
+    n.push_number(1);
+    n.push_number(3);
+    n.call_global(0, 1, block_1)
+}
+
+fn block_1(n: &mut Native) -> Result<bool, VmError> {
+    n.set_global(1).map_err(|e| n.fail(0, e))?; // putchar
+    n.push_number(15);
+    n.push_number(0);
+    n.push_number(1);
+    n.push_number(3);
+    n.call_global(2, 2, block_2)
+}
+
+fn block_2(n: &mut Native) -> Result<bool, VmError> {
+    n.set_global(3).map_err(|e| n.fail(0, e))?; // -
+    n.push_number(13);
+    n.push_number(0);
+    n.push_number(1);
+    n.push_number(3);
+    n.call_global(4, 3, block_3)
+}
+
+fn block_3(n: &mut Native) -> Result<bool, VmError> {
+    n.set_global(5).map_err(|e| n.fail(0, e))?; // <
+    n.push_number(6);
+    n.push_number(0);
+    n.push_number(1);
+    n.push_number(3);
+    n.call_global(6, 4, block_4)
+}
+
+fn block_4(n: &mut Native) -> Result<bool, VmError> {
+    n.set_global(7).map_err(|e| n.fail(0, e))?; // field0
+    n.push_number(2);
+    n.push_number(0);
+    n.push_number(1);
+    n.push_number(3);
+    n.call_global(8, 5, block_5)
+}
+
+fn block_5(n: &mut Native) -> Result<bool, VmError> {
+    n.set_global(9).map_err(|e| n.fail(0, e))?; // arg1
+    n.push_number(1);
+    n.push_number(0);
+    n.push_number(1);
+    n.push_number(3);
+    n.call_global(10, 6, block_6)
+}
+
+fn block_6(n: &mut Native) -> Result<bool, VmError> {
+    n.set_global(11).map_err(|e| n.fail(0, e))?; // id
+    n.push_object(12);
+    n.set_global(13).map_err(|e| n.fail(2, e))?; // count
+    n.push_number(5);
+    n.push_number(1);
+    n.call_global(14, 8, block_8) // count
+}
+
+fn block_7(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(0);
+    n.get_slot(1).map_err(|e| n.fail(1, e))?;
+    n.push_number(2);
+    n.call_global(15, 9, block_9) // <
+}
+
+fn block_8(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(2);
+    n.call_global(16, 10, block_10) // arg1
+}
+
+fn block_9(n: &mut Native) -> Result<bool, VmError> {
+    if n.test().map_err(|e| n.fail(0, e))? { n.goto(11, block_11) } else { n.goto(12, block_12) }
+}
+
+fn block_10(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(65);
+    n.push_number(1);
+    n.call_global(17, 13, block_13) // putchar
+}
+
+fn block_11(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(46);
+    n.push_number(1);
+    n.call_global(18, 14, block_14) // putchar
+}
+
+fn block_12(n: &mut Native) -> Result<bool, VmError> {
+    n.get_global(19).map_err(|e| n.fail(0, e))?;
+    n.push_number(1);
+    n.jump_global(20) // id
+}
+
+fn block_13(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(2);
+    n.call_global(21, 15, block_15) // arg1
+}
+
+fn block_14(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(2);
+    n.call_global(22, 16, block_16) // arg1
+}
+
+fn block_15(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(42);
+    n.push_number(1);
+    n.call_global(23, 17, block_17) // field0
+}
+
+fn block_16(n: &mut Native) -> Result<bool, VmError> {
+    n.get_slot(0).map_err(|e| n.fail(0, e))?;
+    n.push_number(1);
+    n.push_number(2);
+    n.call_global(24, 18, block_18) // -
+}
+
+fn block_17(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(1);
+    n.call_global(25, 19, block_19) // putchar
+}
+
+fn block_18(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(1);
+    n.jump_global(26) // count
+}
+
+fn block_19(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(2);
+    n.call_global(27, 20, block_20) // arg1
+}
+
+fn block_20(n: &mut Native) -> Result<bool, VmError> {
+    n.push_number(10);
+    n.push_number(1);
+    n.jump_global(28) // putchar
+}
@@ -0,0 +1,123 @@
+use ribbit::rvm::{translate, RibField, RunStatus, Vm, VmError};
+
+// The module emitted by `rvm --emit-rust` for
+//
+// (define (count n) (if (< 0 n) (begin (putchar 46) (count (- n 1))) #f))
+// (count 5)
+// (putchar 65)
+// (putchar (field0 42))
+// (putchar 10)
+//
+// compiled with `rsc -t rvm -f+ arity-check -l empty`
+mod count;
+
+const MESSAGE: &str = "Expected a rib reference but got the number 42";
+
+fn interpreted() -> Vm {
+    let mut vm = Vm::new();
+    vm.load(count::RVM_CODE).unwrap();
+    vm.capture_output().unwrap();
+    vm
+}
+
+fn native() -> Vm {
+    let mut vm = Vm::new();
+    count::load(&mut vm).unwrap();
+    vm.capture_output().unwrap();
+    vm
+}
+
+// Runs the program by slices of fuel, with the output of each slice
+fn run_by(vm: &mut Vm, fuel: u64) -> (Vec<String>, Result<RunStatus, VmError>) {
+    let mut outputs = Vec::new();
+    loop {
+        let status = vm.run_for(fuel);
+        outputs.push(vm.take_output().unwrap());
+        if status != Ok(RunStatus::OutOfFuel) {
+            return (outputs, status)
+        }
+    }
+}
+
+// The registers of the program: the next operation and the stack
+fn registers(vm: &Vm) -> (usize, Vec<RibField>) {
+    (vm.pc(), vm.stack())
+}
+
+#[test]
+fn emitted_module_is_up_to_date() {
+    assert_eq!(translate(count::RVM_CODE).unwrap(), include_str!("count.rs"));
+}
+
+#[test]
+fn native_code_runs_as_interpreter() {
+    let (output, status) = run_by(&mut interpreted(), u64::MAX);
+    assert_eq!(output.concat(), ".....A");
+    assert_eq!(status, Err(VmError::TypeError(String::from(MESSAGE))));
+    assert_eq!(run_by(&mut native(), u64::MAX), (output, status));
+}
+
+#[test]
+fn native_code_runs_by_slices_as_interpreter() {
+    for fuel in [1, 2, 3, 5, 7, 10] {
+        assert_eq!(run_by(&mut native(), fuel), run_by(&mut interpreted(), fuel), "fuel {}", fuel);
+    }
+}
+
+#[test]
+fn error_leaves_program_at_failing_operation() {
+    let mut resumed = Vec::new();
+    for mut vm in [interpreted(), native()] {
+        assert_eq!(vm.run(), Err(VmError::TypeError(String::from(MESSAGE))));
+        assert_eq!(vm.take_output().unwrap(), ".....A");
+        // The program goes on from the operation that failed, and not
+        // from the start of its block
+        let status = vm.run();
+        resumed.push((status, vm.take_output().unwrap()));
+    }
+    assert_eq!(resumed[0], resumed[1]);
+}
+
+#[test]
+fn resumes_from_failing_operation_of_block() {
+    let mut vm = native();
+    let mut reference = interpreted();
+    assert_eq!(vm.run(), Err(VmError::TypeError(String::from(MESSAGE))));
+    assert_eq!(reference.run(), Err(VmError::TypeError(String::from(MESSAGE))));
+    // The call to field0 that fails comes after two operations of its
+    // block, and leaves the registers as the interpreter does
+    assert!(registers(&vm) == registers(&reference));
+    loop {
+        let status = vm.run_for(1);
+        assert_eq!(status, reference.run_for(1));
+        assert!(registers(&vm) == registers(&reference));
+        if status != Ok(RunStatus::OutOfFuel) {
+            break
+        }
+    }
+}
+
+#[test]
+fn registers_survive_running_out_of_fuel_in_blocks() {
+    // A block longer than the fuel left is interpreted, so the slices stop
+    // in the middle of blocks too; the registers are those the interpreter
+    // has after as many operations
+    for fuel in [1, 2, 3, 5, 7] {
+        let mut sliced = native();
+        let mut reference = interpreted();
+        loop {
+            let mut status = Ok(RunStatus::OutOfFuel);
+            for _ in 0..4 {
+                status = sliced.run_for(fuel);
+                if status != Ok(RunStatus::OutOfFuel) {
+                    break
+                }
+            }
+            assert_eq!(status, reference.run_for(4 * fuel));
+            assert!(registers(&sliced) == registers(&reference), "fuel {}", fuel);
+            if status != Ok(RunStatus::OutOfFuel) {
+                break
+            }
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use ribbit::rvm::{RibField, RibHeap, RunStatus, Vm, VmError};
+
+mod common;
+
+fn add3(args: &[RibField], _heap: &mut RibHeap) -> Result<RibField, VmError> {
+    let mut sum = 0;
+    for arg in args {
+        sum += arg.as_number().ok_or(VmError::TypeError(String::from("Expected a number")))?;
+    }
+    Ok(RibField::fixnum(sum).unwrap())
+}
+
+#[test]
+fn calls_registered_primitive() {
+    let mut vm = common::load(common::ADD3);
+    vm.register_primitive("add3", 3, add3).unwrap();
+    assert_eq!(vm.run().unwrap(), RunStatus::Halted);
+    assert_eq!(vm.take_output().unwrap(), "!\n");
+}
+
+#[test]
+fn binds_primitive_registered_before_loading() {
+    let mut vm = Vm::new();
+    vm.register_primitive("add3", 3, add3).unwrap();
+    vm.load(common::ADD3).unwrap();
+    vm.capture_output().unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.take_output().unwrap(), "!\n");
+}
+
+#[test]
+fn replaces_registered_primitive() {
+    let mut vm = common::load(common::ADD3);
+    vm.register_primitive("add3", 3, add3).unwrap();
+    vm.register_primitive("add3", 3, |_args, _heap| Ok(RibField::fixnum(63).unwrap())).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.take_output().unwrap(), "?\n");
+}
+
+#[test]
+fn checks_arity_of_registered_primitive() {
+    let mut vm = common::load(common::ADD3_MISSING_ARGUMENT);
+    vm.register_primitive("add3", 3, add3).unwrap();
+    assert_eq!(vm.run(),
+               Err(VmError::ArityMismatch { nargs: 2, expected_nargs: 3, variadic: false }));
+    let mut vm = common::load(common::ADD3_EXTRA_ARGUMENT);
+    vm.register_primitive("add3", 3, add3).unwrap();
+    assert_eq!(vm.run(),
+               Err(VmError::ArityMismatch { nargs: 4, expected_nargs: 3, variadic: false }));
+    assert_eq!(vm.take_output().unwrap(), "");
+}
+
+#[test]
+fn rejects_unknown_primitive() {
+    let mut vm = common::load(common::UNKNOWN_PRIMITIVE);
+    vm.register_primitive("add3", 3, add3).unwrap();
+    assert_eq!(vm.run(), Err(VmError::BadPrimitive(1000)));
+}
@@ -40,15 +40,18 @@
 
 (define eq? eqv?)
 
-(define (equal? x y)
-  (or (eqv? x y)
-      (and (rib? x)
-           (if (eqv? (field2 x) singleton-type)
-               #f
-               (and (rib? y)
-                    (equal? (field2 x) (field2 y))
-                    (equal? (field1 x) (field1 y))
-                    (equal? (field0 x) (field0 y)))))))
+(define equal?
+  (if ##feature-equal?
+      equal? ;; primitive of the host
+      (lambda (x y)
+        (or (eqv? x y)
+            (and (rib? x)
+                 (if (eqv? (field2 x) singleton-type)
+                     #f
+                     (and (rib? y)
+                          (equal? (field2 x) (field2 y))
+                          (equal? (field1 x) (field1 y))
+                          (equal? (field0 x) (field0 y)))))))))
 
 ;;;----------------------------------------------------------------------------
 
@@ -209,14 +212,21 @@
       (type-error)))
 
 (define (string->symbol-aux str syms)
+  (let ((sym (if ##feature-symbol-table-ref
+                 (symbol-table-ref syms str) ;; primitive of the host
+                 (symbol-table-ref-aux syms str))))
+    (or sym
+        (let ((sym (string->uninterned-symbol str)))
+          (set! symtbl (cons sym symtbl))
+          sym))))
+
+(define (symbol-table-ref-aux syms str)
   (if (pair? syms)
       (let ((sym (field0 syms)))
         (if (equal? (field1 sym) str)
             sym
-            (string->symbol-aux str (field1 syms))))
-      (let ((sym (string->uninterned-symbol str)))
-        (set! symtbl (cons sym symtbl))
-        sym)))
+            (symbol-table-ref-aux (field1 syms) str)))
+      #f))
 
 (define symtbl (field1 rib)) ;; get symbol table
 
@@ -226,7 +236,20 @@
 
 ;; Numbers (R4RS section 6.5).
 
-(define (integer? obj) (not (rib? obj)))
+(cond-expand
+  ((host rs)
+   ;; The Rust host also has flonums (and bignums), which are not ribs as
+   ;; far as rib? can tell.
+   (define (number? obj) (not (rib? obj)))
+   (define (integer? obj) ;; not through number?, which programs may redefine
+     (and (not (rib? obj))
+          (or (not (flonum? obj))
+              (eqv? obj (floor obj)))))
+   (define (exact? obj) (not (flonum? obj)))
+   (define inexact? flonum?))
+  (else
+   (define (integer? obj) (not (rib? obj)))
+   (define number? integer?)))
 
 ;;(define rational? integer?)
 ;;(define real? rational?)
@@ -237,17 +260,17 @@
 ;;(define (inexact? obj) #f)
 
 (define (+ x y)
-  (if (and (integer? x) (integer? y))
+  (if (and (number? x) (number? y))
       (%+ x y)
       (type-error)))
 
 (define (- x y)
-  (if (and (integer? x) (integer? y))
+  (if (and (number? x) (number? y))
       (%- x y)
       (type-error)))
 
 (define (* x y)
-  (if (and (integer? x) (integer? y))
+  (if (and (number? x) (number? y))
       (%* x y)
       (type-error)))
 
@@ -258,13 +281,22 @@
           (%quotient x y))
       (type-error)))
 
-(define (= x y)
-  (if (and (integer? x) (integer? y))
-      (eqv? x y)
-      (type-error)))
+(cond-expand
+  ((host rs)
+   (define (= x y)
+     (if (and (number? x) (number? y))
+         (if (or (flonum? x) (flonum? y))
+             (eqv? (exact->inexact x) (exact->inexact y))
+             (eqv? x y))
+         (type-error))))
+  (else
+   (define (= x y)
+     (if (and (number? x) (number? y))
+         (eqv? x y)
+         (type-error)))))
 
 (define (< x y)
-  (if (and (integer? x) (integer? y))
+  (if (and (number? x) (number? y))
       (%< x y)
       (type-error)))
 
@@ -317,10 +349,13 @@
 (define numerator id)
 (define (denominator x) 1)
 
-(define floor id)
-(define ceiling id)
-(define truncate id)
-(define round id)
+(cond-expand
+  ((host rs)) ;; floor, ceiling, truncate, round, exp, sqrt, ... are primitives
+  (else
+   (define floor id)
+   (define ceiling id)
+   (define truncate id)
+   (define round id)))
 
 ;;(define (rationalize x y) ...)
 ;;(define (exp x) ...)
@@ -354,11 +389,14 @@
 
 ;; Integer to string conversion.
 
-(define (number->string x)
-  (list->string
-   (if (< x 0)
-       (cons 45 (number->string-aux (%- 0 x) '()))
-       (number->string-aux x '()))))
+(define number->string
+  (if ##feature-number->string
+      number->string ;; primitive of the host
+      (lambda (x)
+        (list->string
+         (if (< x 0)
+             (cons 45 (number->string-aux (%- 0 x) '()))
+             (number->string-aux x '()))))))
 
 (define (number->string-aux x tail)
   (let ((q (quotient x 10)))
@@ -394,6 +432,18 @@
              (string->number-aux2 (cdr lst) (%- (%* 10 n) (%- c 48)))))
       n))
 
+(cond-expand
+  ((host rs)
+   (define integer->string number->string)
+   (define (number->string x)
+     (if (flonum? x)
+         (flonum->string x)
+         (integer->string x)))
+   (define string->integer string->number)
+   (define (string->number str)
+     (or (string->integer str)
+         (string->flonum str)))))
+
 ;;;----------------------------------------------------------------------------
 
 ;; Characters (R4RS section 6.6).
@@ -430,10 +480,13 @@
 
 (define string? (instance? string-type))
 
-(define (list->string lst)
-  (if (char-list? lst)
-      (rib lst (length lst) string-type)
-      (type-error)))
+(define list->string
+  (if ##feature-list->string
+      list->string ;; primitive of the host
+      (lambda (lst)
+        (if (char-list? lst)
+            (rib lst (length lst) string-type)
+            (type-error)))))
 
 (define (char-list? lst)
   (if (pair? lst)
@@ -508,9 +561,12 @@
         (substring-aux str start i (cons (string-ref str i) tail)))
       (list->string tail)))
 
-(define (string-append str1 str2)
-  (list->string (append (string->list str1)
-                        (string->list str2))))
+(define string-append
+  (if ##feature-string-append
+      string-append ;; primitive of the host
+      (lambda (str1 str2)
+        (list->string (append (string->list str1)
+                              (string->list str2))))))
 
 (define (string-copy str)
   (list->string (append (string->list str) '())))
@@ -560,6 +616,104 @@
 
 ;;;----------------------------------------------------------------------------
 
+;; Weak references and guardians (Rust host only).
+
+(cond-expand
+
+  ((host rs)
+
+   ;; The collector of the Rust host does not trace field0 of weak boxes
+   ;; and guarded objects, nor the key and value of ephemerons. A weak box
+   ;; is cleared when its object is only reachable through weak references,
+   ;; an ephemeron keeps its value only while its key is reachable and a
+   ;; guarded object that is otherwise dead is kept alive and its entry is
+   ;; turned into a pair, for its guardian to return it.
+
+   (define weak-box-type  9)
+   (define ephemeron-type 10)
+   (define guarded-type   11)
+   (define weak-table-type 12)
+   (define guardian-type  13)
+
+   (define weak-box? (instance? weak-box-type))
+
+   (define (make-weak-box obj) (rib obj 0 weak-box-type))
+
+   (define (weak-box-value box) ;; #f once the object is collected
+     (if (weak-box? box)
+         (field0 box)
+         (type-error)))
+
+   ;; Weak tables map keys to values with an association list of
+   ;; ephemerons. The collector sets the key of an entry to #f when the key
+   ;; dies, so #f is not a valid key.
+
+   (define weak-table? (instance? weak-table-type))
+
+   (define (make-weak-table) (rib '() 0 weak-table-type))
+
+   (define (weak-table-entry table key)
+     (weak-table-entry-aux (field0 table) key))
+
+   (define (weak-table-entry-aux entries key)
+     (cond ((null? entries) #f)
+           ((eqv? (field0 (car entries)) key) (car entries))
+           (else (weak-table-entry-aux (cdr entries) key))))
+
+   (define (weak-table-ref table key default)
+     (if (weak-table? table)
+         (let ((entry (and key (weak-table-entry table key))))
+           (if entry (field1 entry) default))
+         (type-error)))
+
+   (define (weak-table-prune table) ;; drops the entries of dead keys
+     (field0-set! table (weak-table-prune-aux (field0 table))))
+
+   (define (weak-table-prune-aux entries)
+     (cond ((null? entries) '())
+           ((field0 (car entries))
+            (cons (car entries) (weak-table-prune-aux (cdr entries))))
+           (else (weak-table-prune-aux (cdr entries)))))
+
+   (define (weak-table-set! table key value)
+     (if (and (weak-table? table) key)
+         (let ((entry (weak-table-entry table key)))
+           (if entry
+               (field1-set! entry value)
+               (begin
+                 (weak-table-prune table)
+                 (field0-set! table
+                              (cons (rib key value ephemeron-type)
+                                    (field0 table))))))
+         (type-error)))
+
+   ;; A guardian links its guarded entries through field1. After a
+   ;; collection the entries of dead objects are pairs.
+
+   (define guardian? (instance? guardian-type))
+
+   (define (make-guardian) (rib 0 0 guardian-type))
+
+   (define (guardian-register! guardian obj)
+     (if (guardian? guardian)
+         (field1-set! guardian (rib obj (field1 guardian) guarded-type))
+         (type-error)))
+
+   (define (guardian-next-aux prev)
+     (let ((entry (field1 prev)))
+       (cond ((eqv? entry 0) #f)
+             ((pair? entry)
+              (field1-set! prev (field1 entry))
+              (field0 entry))
+             (else (guardian-next-aux entry)))))
+
+   (define (guardian-next guardian) ;; a dead object or #f
+     (if (guardian? guardian)
+         (guardian-next-aux guardian)
+         (type-error)))))
+
+;;;----------------------------------------------------------------------------
+
 ;; Control features (R4RS section 6.9).
 
 (define procedure? (instance? procedure-type))
@@ -590,11 +744,13 @@
 (define (call/cc receiver)
   (if (procedure? receiver)
       (let ((c (field1 (field1 (close #f))))) ;; get call/cc continuation rib
-        (receiver (lambda (r)
-                    (let ((c2 (field1 (field1 (close #f)))))
-                      (field0-set! c2 (field0 c)) ;; set "stack" field
-                      (field2-set! c2 (field2 c)) ;; set "pc" field
-                      r)))) ;; return to continuation
+        (let ((handler exception-handler))
+          (receiver (lambda (r)
+                      (let ((c2 (field1 (field1 (close #f)))))
+                        (set! exception-handler handler) ;; handler of continuation
+                        (field0-set! c2 (field0 c)) ;; set "stack" field
+                        (field2-set! c2 (field2 c)) ;; set "pc" field
+                        r))))) ;; return to continuation
       (type-error)))
 
 ;;;----------------------------------------------------------------------------
@@ -797,12 +953,16 @@
         ((symbol? o)
          (display (symbol->string o)))
         ((string? o)
-         (write-chars (string->list o) #f))
+         (if ##feature-write-string
+             (write-string o) ;; primitive of the host
+             (write-chars (string->list o) #f)))
         ((vector? o)
          (putchar 35) ;; #\#
          (write (vector->list o)))
         ((procedure? o)
          (putchar2 35 112)) ;; #p
+        ((error-object? o)
+         (display "#<error-object>"))
         (else
          ;; must be a number
          (display (number->string o)))))
@@ -857,6 +1017,17 @@
   (putchar c1)
   (putchar c2))
 
+(cond-expand
+  ((host rs)
+   ;; Rust values handed to Scheme code by the host and weak references
+   (define display-non-foreign display)
+   (define (display o)
+     (cond ((foreign? o) (display-non-foreign "#<foreign>"))
+           ((weak-box? o) (display-non-foreign "#<weak-box>"))
+           ((weak-table? o) (display-non-foreign "#<weak-table>"))
+           ((guardian? o) (display-non-foreign "#<guardian>"))
+           (else (display-non-foreign o))))))
+
 ;;;----------------------------------------------------------------------------
 
 ;; Compiler from Ribbit Scheme to RVM code.
@@ -1108,12 +1279,18 @@
 (define (type-error)
   (error "*** type error" '()))
 
+;; Errors are raised as error objects, which the REPL reports when there
+;; is no exception handler.
+
 (define (error msg info)
+  (raise (make-error-object msg (cons info '()))))
+
+(define (report-error msg irritants)
   (unwind-and-call
    (lambda ()
+     (set! exception-handler #f) ;; handlers do not survive the unwinding
      (display msg)
-     (display " ")
-     (write info)
+     (for-each (lambda (x) (display " ") (write x)) irritants)
      (newline)
      (repl)
      (exit 0)))) ;; exit program when REPL exited
@@ -1126,3 +1303,49 @@
     (lambda () #f))))
 
 ;;;----------------------------------------------------------------------------
+
+;; Exceptions.
+
+(define exception-handler #f)
+
+(define error-object-type 14)
+
+(define error-object? (instance? error-object-type))
+
+(define (make-error-object msg irritants) (rib msg irritants error-object-type))
+
+(define (error-object-message obj)
+  (if (error-object? obj) (field0 obj) (type-error)))
+
+(define (error-object-irritants obj)
+  (if (error-object? obj) (field1 obj) (type-error)))
+
+(define (with-exception-handler handler thunk)
+  (let ((outer exception-handler))
+    (set! exception-handler
+          (lambda (obj)
+            (set! exception-handler outer) ;; handler runs in outer context
+            (handler obj)))
+    (let ((result (thunk)))
+      (set! exception-handler outer)
+      result)))
+
+(define (raise obj)
+  (if exception-handler
+      (begin
+        (exception-handler obj)
+        (error "*** exception handler returned" obj))
+      (if (error-object? obj)
+          (report-error (error-object-message obj) (error-object-irritants obj))
+          (report-error "*** uncaught exception" (cons obj '())))))
+
+(cond-expand
+
+  ((host rs)
+
+   ;; The Rust host raises the errors it detects itself (wrong type of
+   ;; operand, division by zero, ...) as error objects with no irritants.
+
+   (set-exception-handler! raise)))
+
+;;;----------------------------------------------------------------------------
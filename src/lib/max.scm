@@ -29,15 +29,18 @@
 
 (define eq? eqv?)
 
-(define (equal? x y)
-  (or (eqv? x y)
-      (and (rib? x)
-           (if (eqv? (field2 x) singleton-type)
-               #f
-               (and (rib? y)
-                    (equal? (field2 x) (field2 y))
-                    (equal? (field1 x) (field1 y))
-                    (equal? (field0 x) (field0 y)))))))
+(define equal?
+  (if ##feature-equal?
+      equal? ;; primitive of the host
+      (lambda (x y)
+        (or (eqv? x y)
+            (and (rib? x)
+                 (if (eqv? (field2 x) singleton-type)
+                     #f
+                     (and (rib? y)
+                          (equal? (field2 x) (field2 y))
+                          (equal? (field1 x) (field1 y))
+                          (equal? (field0 x) (field0 y)))))))))
 
 ;;;----------------------------------------------------------------------------
 
@@ -191,14 +194,21 @@
   (string->symbol-aux str symtbl))
 
 (define (string->symbol-aux str syms)
+  (let ((sym (if ##feature-symbol-table-ref
+                 (symbol-table-ref syms str) ;; primitive of the host
+                 (symbol-table-ref-aux syms str))))
+    (or sym
+        (let ((sym (string->uninterned-symbol str)))
+          (set! symtbl (cons sym symtbl))
+          sym))))
+
+(define (symbol-table-ref-aux syms str)
   (if (pair? syms)
       (let ((sym (field0 syms)))
         (if (equal? (field1 sym) str)
             sym
-            (string->symbol-aux str (field1 syms))))
-      (let ((sym (string->uninterned-symbol str)))
-        (set! symtbl (cons sym symtbl))
-        sym)))
+            (symbol-table-ref-aux (field1 syms) str)))
+      #f))
 
 (define symtbl (field1 rib)) ;; get symbol table
 
@@ -305,11 +315,14 @@
 
 ;; Integer to string conversion.
 
-(define (number->string x)
-  (list->string
-   (if (< x 0)
-       (cons 45 (number->string-aux (- 0 x) '()))
-       (number->string-aux x '()))))
+(define number->string
+  (if ##feature-number->string
+      number->string ;; primitive of the host
+      (lambda (x)
+        (list->string
+         (if (< x 0)
+             (cons 45 (number->string-aux (- 0 x) '()))
+             (number->string-aux x '()))))))
 
 (define (number->string-aux x tail)
   (let ((q (quotient x 10)))
@@ -378,7 +391,10 @@
 ;; Strings (R4RS section 6.7).
 
 (define string? (instance? string-type))
-(define (list->string lst) (rib lst (length lst) string-type))
+(define list->string
+  (if ##feature-list->string
+      list->string ;; primitive of the host
+      (lambda (lst) (rib lst (length lst) string-type))))
 (define string->list field0)
 (define string-length field1)
 (define (string-ref str i) (list-ref (field0 str) i))
@@ -427,9 +443,12 @@
         (substring-aux str start i (cons (string-ref str i) tail)))
       (list->string tail)))
 
-(define (string-append str1 str2)
-  (list->string (append (string->list str1)
-                        (string->list str2))))
+(define string-append
+  (if ##feature-string-append
+      string-append ;; primitive of the host
+      (lambda (str1 str2)
+        (list->string (append (string->list str1)
+                              (string->list str2))))))
 
 (define (string-copy str)
   (list->string (append (string->list str) '())))
@@ -688,7 +707,9 @@
         ((symbol? o)
          (display (symbol->string o)))
         ((string? o)
-         (write-chars (string->list o) #f))
+         (if ##feature-write-string
+             (write-string o) ;; primitive of the host
+             (write-chars (string->list o) #f)))
         ((vector? o)
          (putchar 35) ;; #\#
          (write (vector->list o)))
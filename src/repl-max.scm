@@ -175,3 +175,27 @@ or
 cond
 
 )
+
+;; Exported flonum procedures, which only the Rust host has.
+
+(cond-expand
+  ((host rs)
+   (export
+
+/
+number?
+exact?
+inexact?
+exp
+log
+sin
+cos
+tan
+asin
+acos
+atan
+sqrt
+exact->inexact
+inexact->exact
+
+)))
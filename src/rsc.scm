@@ -823,19 +823,34 @@
 
 (define (extract-exports program)
   ;; By default all symbols are exported when the program contains
-  ;; no (export ...) form.
+  ;; no (export ...) form. The forms of a top-level cond-expand are
+  ;; spliced in the program, so that exports can depend on the host.
   (let loop ((lst program) (rev-exprs '()) (exports #f))
     (if (pair? lst)
         (let ((first (car lst)))
-          (if (and (pair? first) (eqv? (car first) 'export))
-              (loop (cdr lst)
-                    rev-exprs
-                    (append (cdr first) (or exports '())))
-              (loop (cdr lst)
-                    (cons first rev-exprs)
-                    exports)))
+          (cond ((and (pair? first) (eqv? (car first) 'export))
+                 (loop (cdr lst)
+                       rev-exprs
+                       (append (cdr first) (or exports '()))))
+                ((and (pair? first) (eqv? (car first) 'cond-expand))
+                 (loop (append (cond-expand-body (cdr first)) (cdr lst))
+                       rev-exprs
+                       exports))
+                (else
+                 (loop (cdr lst)
+                       (cons first rev-exprs)
+                       exports))))
         (cons (reverse rev-exprs) exports))))
 
+(define (cond-expand-body clauses)
+  (if (pair? clauses)
+      (let ((clause (car clauses)))
+        (if (or (eqv? 'else (car clause))
+                (cond-expand-eval (car clause)))
+            (cdr clause)
+            (cond-expand-body (cdr clauses))))
+      '()))
+
 (define (exports->alist exports)
   (if (pair? exports)
       (map (lambda (x)
@@ -1844,6 +1859,14 @@
 
   (for-each (lambda (sym) (scan-opnd sym 3)) predefined)
 
+  (if (and live-features (memq 'bind-exports live-features))
+      ;; exported primitives must be bound even if unused when the host
+      ;; looks them up by name (e.g. Vm::get_global of the Rust host)
+      (for-each (lambda (x)
+                  (if (assq (car x) primitives)
+                      (scan-opnd (car x) 1)))
+                exports))
+
   (scan-proc proc)
 
   (add-init-code! proc)
@@ -1980,16 +2003,15 @@
 (define (extract-features parsed-file)
   (extract
     (lambda (prim acc rec)
-      (case (car prim)
-        ((primitives)
+      (let ((%%k (car prim))) (cond ((memq %k '(primitives))
          (let ((primitives (rec '())))
            (append primitives acc)))
-        ((primitive)
+((memq %k '(primitive))
          (cons prim acc))
-        ((feature)
+((memq %k '(feature))
          (cons prim acc))
-        (else
-         acc)))
+(else
+         acc))))
     parsed-file
     '()))
 
@@ -2182,12 +2204,11 @@
       (let* ((current-features-pair
                (filter-pair
                  (lambda (feature) 
-                   (case (car feature)
-                     ((primitive)
+                   (let ((%%k (car feature))) (cond ((memq %k '(primitive))
                       (memq (caadr feature) used-features))
-                     ((feature)
+((memq %k '(feature))
                       (eval-feature (cadr feature) used-features))
-                     (else (error "Cannot have a feature that is not a primitive or a feature"))))
+(else (error "Cannot have a feature that is not a primitive or a feature")))))
                  features))
              (current-features (car current-features-pair))
              (not-processed (cdr current-features-pair))
@@ -2243,15 +2264,14 @@
 (define (generate-file parsed-file live-features primitives features encode)
   (letrec ((extract-func
               (lambda (prim acc rec)
-                (case (car prim)
-                  ((str)
+                (let ((%%k (car prim))) (cond ((memq %k '(str))
                    (string-append acc (cadr prim)))
-                  ((feature)
+((memq %k '(feature))
                    (let ((condition (cadr prim)))
                      (if (eval-feature condition live-features)
                        (string-append acc (rec ""))
                        acc)))
-                  ((primitives)
+((memq %k '(primitives))
                    (let* ((gen (cdr (soft-assoc 'gen prim)))
                           (generate-one
                             (lambda (prim)
@@ -2275,11 +2295,11 @@
                        acc
                        (apply string-append
                               (map generate-one primitives)))))
-                  ((use-feature)
+((memq %k '(use-feature))
                    (string-append acc (rec "")))
-                  ((primitive)
+((memq %k '(primitive))
                    (string-append acc (rec "")))
-                  ((location)
+((memq %k '(location))
                    (let* ((name (cadr prim))
                           (matched-features
                             (filter 
@@ -2293,15 +2313,15 @@
                      (string-append
                        acc
                        (extract extract-func matched-features ""))))
-                  ((replace)
+((memq %k '(replace))
                    (let* ((pattern     (cadr prim))
                           (pattern     (if (symbol? pattern)
                                          (symbol->string pattern)
                                          pattern))
                           (replacement-text (replace-eval (caddr prim) encode)))
                      (string-append acc (string-replace (rec "") pattern replacement-text))))
-                  (else
-                    acc)))))
+(else
+                    acc))))))
            (extract
              extract-func
              parsed-file